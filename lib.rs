@@ -6,14 +6,14 @@ use ink_lang::contract;
 mod chest {
     use ink_prelude::vec::Vec;
     use ink_storage::{
-        collections::HashMap as StorageHashMap,
+        collections::{HashMap as StorageHashMap, Vec as StorageVec},
         traits::{PackedLayout, SpreadLayout},
     };
     use ink_env::{
         self,
         hash::Blake2x256,
         Clear,
-        call::{FromAccountId, Selector},
+        call::{build_call, utils::ReturnType, ExecutionInput, FromAccountId, Selector},
         AccountId,
     };
     use ink_primitives::{
@@ -25,6 +25,57 @@ mod chest {
         },
     };
 
+    /// Denominator of basis point values such as the collateral ratio.
+    const BASIS_POINTS: u128 = 10_000;
+
+    /// Fixed-point unit of the rebase scaling factor, representing `1.0`.
+    const SCALING_UNIT: u128 = 1_000_000_000_000_000_000;
+
+    /// Storage layout version this code reads and writes.
+    const STORAGE_VERSION: u16 = 2;
+
+    /// Longest name or symbol, in bytes, the owner may set.
+    const MAX_METADATA_LENGTH: usize = 64;
+
+    /// Fixed-point precision of the dividend accumulator.
+    const DIVIDEND_PRECISION: u128 = 1_000_000_000_000;
+
+    /// `capabilities` bit set when new tokens can be minted.
+    pub const CAPABILITY_MINTABLE: u32 = 1 << 0;
+    /// `capabilities` bit set when holders can burn tokens.
+    pub const CAPABILITY_BURNABLE: u32 = 1 << 1;
+    /// `capabilities` bit set when the contract can be paused.
+    pub const CAPABILITY_PAUSABLE: u32 = 1 << 2;
+    /// `capabilities` bit set when allowances can be granted through `permit`.
+    pub const CAPABILITY_PERMIT: u32 = 1 << 3;
+
+    /// Identifier of an access control role, the `Blake2x256` hash of its name.
+    pub type RoleId = [u8; 32];
+
+    /// `Blake2x256("ADMIN_ROLE")`: may grant and revoke every role.
+    pub const ADMIN_ROLE: RoleId = [
+        0x66, 0xdb, 0xd5, 0x44, 0xf0, 0x72, 0x10, 0x2c, 0x1d, 0xfe, 0x83, 0xf7, 0xe4, 0x52, 0x88, 0x52,
+        0x2d, 0x45, 0x9c, 0x77, 0x2d, 0xef, 0xa2, 0x32, 0x95, 0xbd, 0x7b, 0x65, 0x16, 0x3b, 0xc7, 0x9b,
+    ];
+
+    /// `Blake2x256("MINTER_ROLE")`: may mint new tokens.
+    pub const MINTER_ROLE: RoleId = [
+        0xdd, 0x44, 0x72, 0x48, 0x09, 0x8c, 0x38, 0xa0, 0x3c, 0x57, 0x90, 0x24, 0x5a, 0x36, 0xba, 0x2c,
+        0x24, 0x6a, 0x69, 0xc3, 0xae, 0xf4, 0x4e, 0x27, 0x1f, 0xc8, 0x77, 0x17, 0xb2, 0x5e, 0xa9, 0xed,
+    ];
+
+    /// `Blake2x256("PAUSER_ROLE")`: may pause and unpause the contract.
+    pub const PAUSER_ROLE: RoleId = [
+        0x5f, 0x6f, 0xed, 0x67, 0x1d, 0xfe, 0x85, 0x37, 0xcf, 0x30, 0x61, 0xc4, 0xaf, 0x9a, 0xc2, 0x55,
+        0x81, 0x31, 0x91, 0xa8, 0xad, 0x64, 0x37, 0xfb, 0x07, 0x21, 0x46, 0xe7, 0x46, 0x9d, 0x84, 0xd4,
+    ];
+
+    /// Storage of the contract.
+    ///
+    /// The code can be replaced through `set_code_hash` while this storage stays
+    /// in place, so every upgrade must keep the existing fields in their current
+    /// order and with their current types. New fields may only be appended, along
+    /// with a `STORAGE_VERSION` bump and a `migrate` step initializing them.
     #[ink(storage)]
     pub struct Chest {
         total_supply: u128,
@@ -36,11 +87,636 @@ mod chest {
         collateral_pool: u128,
         collateral_address: AccountId,
         collateral_price: u128,
+        /// Number of decimals the collateral price is expressed in.
+        price_decimals: u8,
+        collateral_ratio: u128,
+        max_supply: u128,
+        owner: AccountId,
+        paused: bool,
+        frozen: StorageHashMap<AccountId, bool>,
+        /// Set while a message that calls into the collateral contract is running.
+        locked: bool,
+        nonces: StorageHashMap<AccountId, u128>,
+        current_snapshot_id: u128,
+        /// Per-account `(snapshot_id, balance)` checkpoints, recorded lazily the
+        /// first time a balance changes after a snapshot.
+        account_snapshots: StorageHashMap<AccountId, Vec<(u128, u128)>>,
+        total_supply_snapshots: Vec<(u128, u128)>,
+        fee_bps: u128,
+        fee_collector: AccountId,
+        /// Collateralization ratio, in basis points, below which liquidations open.
+        liquidation_ratio: u128,
+        /// Extra collateral, in basis points, paid to liquidators.
+        liquidation_bonus: u128,
+        /// Collateralization ratio, in basis points, the pool must keep after a mint.
+        min_collateral_ratio: u128,
+        total_minted: u128,
+        total_redeemed: u128,
+        roles: StorageHashMap<(RoleId, AccountId), bool>,
+        vesting: StorageHashMap<AccountId, VestingSchedule>,
+        /// Multiplier from the raw amounts stored in `balances` to token balances,
+        /// in units of `SCALING_UNIT`. Rebases change it instead of every balance.
+        scaling_factor: u128,
+        staked: StorageHashMap<AccountId, u128>,
+        /// Block since which each staker's rewards have not been settled.
+        stake_blocks: StorageHashMap<AccountId, BlockNumber>,
+        unclaimed_rewards: StorageHashMap<AccountId, u128>,
+        /// Reward per staked token per block, in basis points.
+        reward_rate: u128,
+        /// Share of redeemed collateral, in basis points, kept in the pool.
+        redeem_fee_bps: u128,
+        /// Cumulative collateral distributed per token, scaled by `DIVIDEND_PRECISION`.
+        reward_per_token: u128,
+        /// Value of `reward_per_token` up to which each account's dividends are settled.
+        reward_debt: StorageHashMap<AccountId, u128>,
+        dividends_owed: StorageHashMap<AccountId, u128>,
+        /// Account nominated by `transfer_ownership`, awaiting `accept_ownership`.
+        pending_owner: Option<AccountId>,
+        /// Holder list of storage version 1, moved into `holders` by `migrate`.
+        legacy_holders: Vec<AccountId>,
+        /// Position of each holder in `holders`.
+        holder_index: StorageHashMap<AccountId, u32>,
+        /// Timestamp after which an allowance can no longer be spent. Allowances
+        /// without an entry never expire.
+        allowance_expiries: StorageHashMap<(AccountId, AccountId), u64>,
+        /// Collateral types accepted on top of `collateral_address`, whose pool and
+        /// price stay in the fields above.
+        collaterals: StorageHashMap<AccountId, CollateralInfo>,
+        /// Timestamp of the last change to `collateral_price`.
+        price_updated_at: u64,
+        /// Age after which `collateral_price` may no longer back mints and
+        /// redemptions, `0` meaning it never goes stale.
+        max_price_age: u64,
+        /// Milliseconds an account must wait between mints, `0` meaning no cooldown.
+        mint_cooldown: u64,
+        last_mint_at: StorageHashMap<AccountId, u64>,
+        /// Native balance deposited through `deposit`, backing wrapped tokens 1:1.
+        native_reserve: Balance,
+        /// Transfer, flash mint and redeem fees taken over the contract's lifetime.
+        total_fees_collected: u128,
+        /// Part of `total_fees_collected` received by each collector; redeem fees
+        /// are booked to this contract since they stay in the pool.
+        fees_collected: StorageHashMap<AccountId, u128>,
+        /// Collateral locked in each account's debt position, kept apart from
+        /// `collateral_pool`.
+        position_collateral: StorageHashMap<AccountId, u128>,
+        debt: StorageHashMap<AccountId, u128>,
+        /// Lowest collateral value to debt ratio, in basis points, a position may
+        /// be opened or extended at.
+        position_min_ratio: u128,
+        /// Smallest amount `mint` and `mint_to` accept, keeping dust positions out.
+        min_mint_amount: u128,
+        /// Primary collateral each account has deposited through minting and not
+        /// yet redeemed.
+        collateral_contributed: StorageHashMap<AccountId, u128>,
+        /// Whether a non-zero allowance must be reset to zero before `approve` can
+        /// change it.
+        strict_approve: bool,
+        /// Band the collateral price has to stay within to be accepted.
+        min_price: u128,
+        max_price: u128,
+        /// Delay timelocked actions must be queued for, `0` disabling the timelock.
+        min_delay: u64,
+        /// Earliest execution time of each queued action, keyed by `action_id`.
+        queued_actions: StorageHashMap<[u8; 32], u64>,
+        /// Contract asked to approve every transfer, if any.
+        compliance_contract: Option<AccountId>,
+        storage_version: u16,
+        /// Balances below this that a `transfer` would leave behind are sent along,
+        /// `0` disabling the sweep.
+        dust_threshold: u128,
+        /// Whether zero-amount transfers go through as ERC-20 requires.
+        allow_zero_transfers: bool,
+        /// Most an account can send per `velocity_window`, `0` disabling the limit.
+        max_transfer_per_window: u128,
+        velocity_window: u64,
+        /// Start of each account's current window and what it has sent in it.
+        transfer_windows: StorageHashMap<AccountId, (u64, u128)>,
+        /// Collateral claims of burned tokens, oldest first. Entries before
+        /// `redemption_queue_head` have been paid out.
+        redemption_queue: StorageVec<(AccountId, u128)>,
+        pending_redemptions: StorageHashMap<AccountId, u128>,
+        /// Sum of all queued claims, reserved out of `collateral_pool`.
+        redemption_queued: u128,
+        /// How much each capped minter can still mint through `mint_as`.
+        mint_allowance: StorageHashMap<AccountId, u128>,
+        /// Every spender each owner has approved, for `revoke_all`.
+        spenders: StorageHashMap<AccountId, Vec<AccountId>>,
+        /// Rounding of the collateral taken on mint and paid out on redeem.
+        mint_rounding: RoundingMode,
+        redeem_rounding: RoundingMode,
+        /// Account allowed to book collateral yield, if any.
+        yield_source: Option<AccountId>,
+        total_yield_accrued: u128,
+        /// Share of the pool, in basis points, kept back from `withdraw_collateral`.
+        reserve_bps: u128,
+        /// Whether transfers are limited to accounts in `transfer_whitelist`.
+        whitelist_enabled: bool,
+        transfer_whitelist: StorageHashMap<AccountId, bool>,
+        /// Accounts such as the treasury whose balances `circulating_supply` leaves out.
+        non_circulating: Vec<AccountId>,
+        /// Backing ratio, in basis points, below which `check_health` pauses the
+        /// contract, `0` disabling the check.
+        insolvency_threshold: u128,
+        /// Largest amount a single transfer can move, `0` for no limit.
+        max_tx_amount: u128,
+        /// Whether transfers out of the owner's account skip `max_tx_amount`.
+        owner_exempt_from_max_tx: bool,
+        /// How long primary collateral paid out by redeems stays escrowed, `0`
+        /// paying out right away.
+        redeem_delay: u64,
+        /// Escrowed collateral of each account and when it is released.
+        pending_collateral: StorageHashMap<AccountId, (u128, u64)>,
+        /// Accounts whose transfers, in or out, pay no transfer fee.
+        fee_exempt: StorageHashMap<AccountId, bool>,
+        /// Primary collateral that redeems, withdrawals and liquidations must leave
+        /// in the pool.
+        collateral_floor: u128,
+        /// Accounts that confirm multisig transactions, empty if the single owner
+        /// acts alone.
+        multisig_owners: Vec<AccountId>,
+        threshold: u8,
+        /// Submitted multisig transactions until they are executed.
+        transactions: StorageHashMap<u32, MultisigAction>,
+        confirmations: StorageHashMap<(u32, AccountId), bool>,
+        next_transaction_id: u32,
+        /// `Blake2x256` hash of the compressed public key whose signed prices
+        /// replace the collateral contract's, if any.
+        oracle: Option<AccountId>,
+        /// Most primary collateral mints and donations can fill the pool with, `0`
+        /// for no cap.
+        max_collateral_pool: u128,
+        /// Fee, in basis points of the borrowed amount, charged on flash mints.
+        flash_fee_bps: u128,
+        /// Tokens that may be minted within one block, `0` meaning unlimited.
+        max_mint_per_block: u128,
+        /// Tokens that may be redeemed within one block, `0` meaning unlimited.
+        max_redeem_per_block: u128,
+        mint_block: BlockNumber,
+        minted_in_block: u128,
+        redeem_block: BlockNumber,
+        redeemed_in_block: u128,
+        /// Accounts with a non-zero balance, in no particular order.
+        holders: StorageVec<AccountId>,
+        /// Sum of the raw amounts in `balances`, which dividends are split over.
+        held_raw_supply: u128,
+        /// Position of the oldest claim in `redemption_queue` still waiting.
+        redemption_queue_head: u32,
+        /// Sum of every position's debt, backed by position collateral rather than
+        /// the pools.
+        total_debt: u128,
+        /// Sum of every position's collateral.
+        total_position_collateral: u128,
+        /// Sum of all escrowed collateral in `pending_collateral`.
+        total_escrowed: u128,
+        /// Collateral distributed as dividends and not claimed yet.
+        dividend_reserve: u128,
+        /// Part of `fees_collected` each collector received since its last sweep.
+        unswept_fees: StorageHashMap<AccountId, u128>,
+    }
+
+    /// Errors that can occur upon calling this contract.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if the account does not hold enough tokens.
+        InsufficientBalance,
+        /// Returned if the spender's allowance does not cover the amount.
+        InsufficientAllowance,
+        /// Returned if the collateral pool cannot cover the operation.
+        InsufficientCollateral,
+        /// Returned if the contract is paused.
+        ContractPaused,
+        /// Returned if the sender or recipient is frozen.
+        AccountFrozen,
+        /// Returned if the caller lacks the role the operation needs.
+        MissingRole,
+        /// Returned if the amount is below `min_mint_amount`.
+        BelowMinimumMint,
+        /// Returned if minting would take the supply past `max_supply`.
+        MaxSupplyExceeded,
+        /// Returned if the per-block mint limit would be exceeded.
+        MintRateLimitExceeded,
+        /// Returned if the recipient's mint cooldown has not passed.
+        MintCooldownActive,
+        /// Returned if the collateral price is outside the accepted bounds.
+        PriceOutOfBounds,
+        /// Returned if the collateral price is older than `max_price_age`.
+        StalePrice,
+        /// Returned if the amounts involved overflow.
+        ArithmeticOverflow,
+        /// Returned if the collateral would take the pool past `max_collateral_pool`.
+        CollateralCapReached,
+        /// Returned if the recipient is the zero address.
+        ZeroAddress,
+        /// Returned if the amount is zero while zero-amount transfers are disabled.
+        ZeroAmount,
+        /// Returned if the sender or recipient is not on the transfer whitelist.
+        NotWhitelisted,
+        /// Returned if the amount is above `max_tx_amount`.
+        MaxTxAmountExceeded,
+        /// Returned if the compliance contract rejects the transfer.
+        TransferNotCompliant,
+        /// Returned if the sender would exceed its `max_transfer_per_window`.
+        TransferVelocityExceeded,
+    }
+
+    /// The contract result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    /// Errors of the PSP22 messages, encoded as the standard's `PSP22Error`.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP22Error {
+        /// Any error without a PSP22 counterpart, named after the `Error` variant.
+        Custom(String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(String),
+    }
+
+    impl From<Error> for PSP22Error {
+        fn from(error: Error) -> Self {
+            match error {
+                Error::InsufficientBalance => PSP22Error::InsufficientBalance,
+                Error::InsufficientAllowance => PSP22Error::InsufficientAllowance,
+                Error::ZeroAddress => PSP22Error::ZeroRecipientAddress,
+                error => PSP22Error::Custom(ink_prelude::format!("{:?}", error)),
+            }
+        }
+    }
+
+    /// The result type of the PSP22 messages.
+    pub type PSP22Result<T> = core::result::Result<T, PSP22Error>;
+
+    /// Pool and last known price of an additional collateral type. Prices share
+    /// `price_decimals` with the primary collateral.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct CollateralInfo {
+        pool: u128,
+        price: u128,
+    }
+
+    /// How collateral amounts that don't divide evenly are rounded.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub enum RoundingMode {
+        Down,
+        Up,
+        /// Halves round up.
+        Nearest,
+    }
+
+    /// Owner operations that need `threshold` multisig owners to confirm while
+    /// the multisig is set up.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub enum MultisigAction {
+        SetMaxSupply(u128),
+        SetCodeHash([u8; 32]),
+        WithdrawCollateral(u128),
+    }
+
+    /// Owner operations that have to be queued through the timelock while
+    /// `min_delay` is non-zero.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum TimelockAction {
+        SetCollateralRatio(u128),
+        WithdrawCollateral(u128),
+        SetCodeHash([u8; 32]),
+        SetMinDelay(u64),
+    }
+
+    /// Tokens escrowed for a beneficiary that unlock linearly over `duration`
+    /// milliseconds starting at `start`.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout))]
+    pub struct VestingSchedule {
+        total: u128,
+        released: u128,
+        start: u64,
+        duration: u64,
+    }
+
+    /// Computes `a * b / c` without overflowing on the intermediate product; returns
+    /// `None` if the result does not fit into a `u128`.
+    fn mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+        u128::try_from(U256::from(a) * U256::from(b) / U256::from(c)).ok()
+    }
+
+    /// Computes `a * b / c` rounded as `rounding` says; returns `None` on overflow,
+    /// division by zero or if the result does not fit into a `u128`.
+    fn mul_div_round(a: U256, b: U256, c: U256, rounding: RoundingMode) -> Option<u128> {
+        let product = a.checked_mul(b)?;
+        let quotient = product.checked_div(c)?;
+        let remainder = product % c;
+        let round_up = match rounding {
+            RoundingMode::Down => false,
+            RoundingMode::Up => !remainder.is_zero(),
+            RoundingMode::Nearest => remainder >= c - remainder,
+        };
+        let quotient = if round_up { quotient.checked_add(U256::from(1))? } else { quotient };
+        u128::try_from(quotient).ok()
+    }
+
+    /// Messages `Chest` calls on the collateral contract.
+    pub trait CollateralToken {
+        /// Current price of the collateral, or `None` if the query failed.
+        fn price(&self) -> Option<u128>;
+
+        /// Moves `value` collateral from `from` to `to` using the caller's allowance.
+        /// Returns `false` if the transfer failed.
+        fn transfer_from(&mut self, from: AccountId, to: AccountId, value: u128) -> bool;
+
+        /// Moves `value` collateral from the caller to `to`.
+        /// Returns `false` if the transfer failed.
+        fn transfer(&mut self, to: AccountId, value: u128) -> bool;
+
+        /// Collateral held by `owner`, or 0 if the query failed.
+        fn balance_of(&self, owner: AccountId) -> u128;
+    }
+
+    /// Cross-contract reference to the collateral contract at `collateral_address`.
+    pub struct CollateralRef {
+        account_id: AccountId,
+    }
+
+    impl FromAccountId<Environment> for CollateralRef {
+        fn from_account_id(account_id: AccountId) -> Self {
+            Self { account_id }
+        }
+    }
+
+    #[cfg(not(test))]
+    impl CollateralToken for CollateralRef {
+        fn price(&self) -> Option<u128> {
+            build_call::<Environment>()
+                .callee(self.account_id)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(ExecutionInput::new(Selector::new(ink_lang::selector_bytes!("price"))))
+                .returns::<ReturnType<u128>>()
+                .fire()
+                .ok()
+        }
+
+        fn transfer_from(&mut self, from: AccountId, to: AccountId, value: u128) -> bool {
+            build_call::<Environment>()
+                .callee(self.account_id)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink_lang::selector_bytes!("transfer_from")))
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(value),
+                )
+                .returns::<ReturnType<bool>>()
+                .fire()
+                .unwrap_or(false)
+        }
+
+        fn transfer(&mut self, to: AccountId, value: u128) -> bool {
+            build_call::<Environment>()
+                .callee(self.account_id)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink_lang::selector_bytes!("transfer")))
+                        .push_arg(to)
+                        .push_arg(value),
+                )
+                .returns::<ReturnType<bool>>()
+                .fire()
+                .unwrap_or(false)
+        }
+
+        fn balance_of(&self, owner: AccountId) -> u128 {
+            build_call::<Environment>()
+                .callee(self.account_id)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(ExecutionInput::new(Selector::new(ink_lang::selector_bytes!("balance_of"))).push_arg(owner))
+                .returns::<ReturnType<u128>>()
+                .fire()
+                .unwrap_or(0)
+        }
+    }
+
+    /// The off-chain test environment cannot dispatch cross-contract calls, so
+    /// under test the collateral contract is served by [`mock`].
+    #[cfg(test)]
+    impl CollateralToken for CollateralRef {
+        fn price(&self) -> Option<u128> {
+            mock::price(self.account_id)
+        }
+
+        fn transfer_from(&mut self, from: AccountId, to: AccountId, value: u128) -> bool {
+            mock::transfer(self.account_id, from, to, value)
+        }
+
+        fn transfer(&mut self, to: AccountId, value: u128) -> bool {
+            mock::transfer(self.account_id, ink_env::account_id::<Environment>(), to, value)
+        }
+
+        fn balance_of(&self, owner: AccountId) -> u128 {
+            mock::balance_of(self.account_id, owner)
+        }
+    }
+
+    /// Callback `Chest` invokes on the target of a flash mint.
+    pub trait FlashBorrower {
+        /// Called after `amount` tokens were minted to the borrower, who must hold
+        /// `amount + fee` once it returns. Returns `false` if the callback failed.
+        fn on_flash_mint(&mut self, initiator: AccountId, amount: u128, fee: u128, data: Vec<u8>) -> bool;
+    }
+
+    /// Cross-contract reference to a flash mint borrower.
+    pub struct FlashBorrowerRef {
+        account_id: AccountId,
+    }
+
+    impl FromAccountId<Environment> for FlashBorrowerRef {
+        fn from_account_id(account_id: AccountId) -> Self {
+            Self { account_id }
+        }
+    }
+
+    #[cfg(not(test))]
+    impl FlashBorrower for FlashBorrowerRef {
+        fn on_flash_mint(&mut self, initiator: AccountId, amount: u128, fee: u128, data: Vec<u8>) -> bool {
+            build_call::<Environment>()
+                .callee(self.account_id)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink_lang::selector_bytes!("on_flash_mint")))
+                        .push_arg(initiator)
+                        .push_arg(amount)
+                        .push_arg(fee)
+                        .push_arg(data),
+                )
+                .returns::<ReturnType<bool>>()
+                .fire()
+                .unwrap_or(false)
+        }
+    }
+
+    #[cfg(test)]
+    impl FlashBorrower for FlashBorrowerRef {
+        fn on_flash_mint(&mut self, _initiator: AccountId, _amount: u128, _fee: u128, _data: Vec<u8>) -> bool {
+            mock::callback_result(self.account_id)
+        }
+    }
+
+    /// Hook `Chest` invokes on contract recipients of `safe_transfer`.
+    pub trait TokenRecipient {
+        /// Called after `amount` tokens were credited from `from`. Returns `false`
+        /// to reject them.
+        fn on_received(&mut self, from: AccountId, amount: u128, data: Vec<u8>) -> bool;
+    }
+
+    /// Cross-contract reference to a `safe_transfer` recipient.
+    pub struct TokenRecipientRef {
+        account_id: AccountId,
+    }
+
+    impl FromAccountId<Environment> for TokenRecipientRef {
+        fn from_account_id(account_id: AccountId) -> Self {
+            Self { account_id }
+        }
+    }
+
+    #[cfg(not(test))]
+    impl TokenRecipient for TokenRecipientRef {
+        fn on_received(&mut self, from: AccountId, amount: u128, data: Vec<u8>) -> bool {
+            build_call::<Environment>()
+                .callee(self.account_id)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink_lang::selector_bytes!("on_received")))
+                        .push_arg(from)
+                        .push_arg(amount)
+                        .push_arg(data),
+                )
+                .returns::<ReturnType<bool>>()
+                .fire()
+                .unwrap_or(false)
+        }
+    }
+
+    #[cfg(test)]
+    impl TokenRecipient for TokenRecipientRef {
+        fn on_received(&mut self, _from: AccountId, _amount: u128, _data: Vec<u8>) -> bool {
+            mock::callback_result(self.account_id)
+        }
+    }
+
+    /// Exchange `Chest` routes its collateral through when switching backing assets.
+    pub trait CollateralSwap {
+        /// Swaps `amount_in` of `token_in`, already sent to the exchange, for
+        /// `token_out` sent back to the caller. Returns the amount sent back.
+        fn swap(&mut self, token_in: AccountId, token_out: AccountId, amount_in: u128) -> u128;
+    }
+
+    /// Cross-contract reference to a collateral exchange.
+    pub struct CollateralSwapRef {
+        account_id: AccountId,
+    }
+
+    impl FromAccountId<Environment> for CollateralSwapRef {
+        fn from_account_id(account_id: AccountId) -> Self {
+            Self { account_id }
+        }
+    }
+
+    #[cfg(not(test))]
+    impl CollateralSwap for CollateralSwapRef {
+        fn swap(&mut self, token_in: AccountId, token_out: AccountId, amount_in: u128) -> u128 {
+            build_call::<Environment>()
+                .callee(self.account_id)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink_lang::selector_bytes!("swap")))
+                        .push_arg(token_in)
+                        .push_arg(token_out)
+                        .push_arg(amount_in),
+                )
+                .returns::<ReturnType<u128>>()
+                .fire()
+                .unwrap_or(0)
+        }
+    }
+
+    #[cfg(test)]
+    impl CollateralSwap for CollateralSwapRef {
+        fn swap(&mut self, _token_in: AccountId, token_out: AccountId, _amount_in: u128) -> u128 {
+            let amount_out = mock::swap_output(self.account_id);
+            if mock::transfer(token_out, self.account_id, ink_env::account_id::<Environment>(), amount_out) {
+                amount_out
+            } else {
+                0
+            }
+        }
+    }
+
+    /// External module `Chest` asks to approve transfers.
+    pub trait Compliance {
+        /// Returns whether `from` may send `amount` tokens to `to`.
+        fn can_transfer(&self, from: AccountId, to: AccountId, amount: u128) -> bool;
+    }
+
+    /// Cross-contract reference to a compliance module.
+    pub struct ComplianceRef {
+        account_id: AccountId,
+    }
+
+    impl FromAccountId<Environment> for ComplianceRef {
+        fn from_account_id(account_id: AccountId) -> Self {
+            Self { account_id }
+        }
+    }
+
+    #[cfg(not(test))]
+    impl Compliance for ComplianceRef {
+        fn can_transfer(&self, from: AccountId, to: AccountId, amount: u128) -> bool {
+            build_call::<Environment>()
+                .callee(self.account_id)
+                .gas_limit(0)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink_lang::selector_bytes!("can_transfer")))
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(amount),
+                )
+                .returns::<ReturnType<bool>>()
+                .fire()
+                .unwrap_or(false)
+        }
+    }
+
+    #[cfg(test)]
+    impl Compliance for ComplianceRef {
+        fn can_transfer(&self, _from: AccountId, to: AccountId, _amount: u128) -> bool {
+            mock::callback_result(self.account_id) && !mock::is_blocked(self.account_id, to)
+        }
     }
 
     impl Chest {
         #[ink(constructor)]
-        pub fn new(name: String, symbol: String, decimals: u8, collateral_address: AccountId, collateral_price: u128) -> Self {
+        pub fn new(name: String, symbol: String, decimals: u8, collateral_address: AccountId, collateral_price: u128, price_decimals: u8, collateral_ratio: u128, max_supply: u128) -> Self {
+            assert!(!name.is_empty(), "Name must not be empty");
+            assert!(!symbol.is_empty(), "Symbol must not be empty");
+            assert!(decimals <= 36, "Decimals too large");
+            assert!(collateral_price > 0, "Collateral price must be positive");
+            assert!(10u128.checked_pow(u32::from(price_decimals)).is_some(), "Price decimals too large");
             let mut instance = Self {
                 name,
                 symbol,
@@ -51,7 +727,163 @@ mod chest {
                 collateral_pool: 0,
                 collateral_address,
                 collateral_price,
+                price_decimals,
+                collateral_ratio,
+                max_supply,
+                owner: Self::env().caller(),
+                paused: false,
+                frozen: StorageHashMap::new(),
+                locked: false,
+                nonces: StorageHashMap::new(),
+                current_snapshot_id: 0,
+                account_snapshots: StorageHashMap::new(),
+                total_supply_snapshots: Vec::new(),
+                fee_bps: 0,
+                fee_collector: Self::env().caller(),
+                liquidation_ratio: BASIS_POINTS,
+                liquidation_bonus: 0,
+                min_collateral_ratio: 0,
+                total_minted: 0,
+                total_redeemed: 0,
+                roles: StorageHashMap::new(),
+                vesting: StorageHashMap::new(),
+                scaling_factor: SCALING_UNIT,
+                staked: StorageHashMap::new(),
+                stake_blocks: StorageHashMap::new(),
+                unclaimed_rewards: StorageHashMap::new(),
+                reward_rate: 0,
+                redeem_fee_bps: 0,
+                reward_per_token: 0,
+                reward_debt: StorageHashMap::new(),
+                dividends_owed: StorageHashMap::new(),
+                pending_owner: None,
+                legacy_holders: Vec::new(),
+                holder_index: StorageHashMap::new(),
+                allowance_expiries: StorageHashMap::new(),
+                collaterals: StorageHashMap::new(),
+                price_updated_at: Self::env().block_timestamp(),
+                max_price_age: 0,
+                mint_cooldown: 0,
+                last_mint_at: StorageHashMap::new(),
+                native_reserve: 0,
+                total_fees_collected: 0,
+                fees_collected: StorageHashMap::new(),
+                position_collateral: StorageHashMap::new(),
+                debt: StorageHashMap::new(),
+                position_min_ratio: BASIS_POINTS,
+                min_mint_amount: 0,
+                collateral_contributed: StorageHashMap::new(),
+                strict_approve: false,
+                min_price: 0,
+                max_price: u128::MAX,
+                min_delay: 0,
+                queued_actions: StorageHashMap::new(),
+                compliance_contract: None,
+                storage_version: STORAGE_VERSION,
+                dust_threshold: 0,
+                allow_zero_transfers: true,
+                max_transfer_per_window: 0,
+                velocity_window: 0,
+                transfer_windows: StorageHashMap::new(),
+                redemption_queue: StorageVec::new(),
+                pending_redemptions: StorageHashMap::new(),
+                redemption_queued: 0,
+                mint_allowance: StorageHashMap::new(),
+                spenders: StorageHashMap::new(),
+                mint_rounding: RoundingMode::Up,
+                redeem_rounding: RoundingMode::Down,
+                yield_source: None,
+                total_yield_accrued: 0,
+                reserve_bps: 0,
+                whitelist_enabled: false,
+                transfer_whitelist: StorageHashMap::new(),
+                non_circulating: Vec::new(),
+                insolvency_threshold: 0,
+                max_tx_amount: 0,
+                owner_exempt_from_max_tx: false,
+                redeem_delay: 0,
+                pending_collateral: StorageHashMap::new(),
+                fee_exempt: StorageHashMap::new(),
+                collateral_floor: 0,
+                multisig_owners: Vec::new(),
+                threshold: 0,
+                transactions: StorageHashMap::new(),
+                confirmations: StorageHashMap::new(),
+                next_transaction_id: 0,
+                oracle: None,
+                max_collateral_pool: 0,
+                flash_fee_bps: 0,
+                max_mint_per_block: 0,
+                max_redeem_per_block: 0,
+                mint_block: 0,
+                minted_in_block: 0,
+                redeem_block: 0,
+                redeemed_in_block: 0,
+                holders: StorageVec::new(),
+                held_raw_supply: 0,
+                redemption_queue_head: 0,
+                total_debt: 0,
+                total_position_collateral: 0,
+                total_escrowed: 0,
+                dividend_reserve: 0,
+                unswept_fees: StorageHashMap::new(),
             };
+            let deployer = Self::env().caller();
+            for role in [ADMIN_ROLE, MINTER_ROLE, PAUSER_ROLE] {
+                instance.roles.insert((role, deployer), true);
+            }
+            instance
+        }
+
+        /// Deploys a "Chest"/"CHEST" token with 18 decimals, a collateral price of 1.00,
+        /// a 100% collateral ratio and no supply cap.
+        #[ink(constructor)]
+        pub fn new_default(collateral_address: AccountId) -> Self {
+            Self::new(String::from("Chest"), String::from("CHEST"), 18, collateral_address, 100, 2, 10_000, 0)
+        }
+
+        /// Deploys the token and mints `initial_supply` to the deployer, who pays the
+        /// collateral just like for `mint`.
+        #[ink(constructor)]
+        pub fn new_with_supply(
+            name: String,
+            symbol: String,
+            decimals: u8,
+            collateral_address: AccountId,
+            collateral_price: u128,
+            price_decimals: u8,
+            collateral_ratio: u128,
+            max_supply: u128,
+            initial_supply: u128,
+        ) -> Self {
+            let mut instance = Self::new(name, symbol, decimals, collateral_address, collateral_price, price_decimals, collateral_ratio, max_supply);
+            let _ = instance.mint(initial_supply);
+            instance
+        }
+
+        /// Deploys the token with max supply changes, code upgrades and collateral
+        /// withdrawals requiring `threshold` of `owners` to confirm.
+        #[ink(constructor)]
+        pub fn new_with_owners(
+            name: String,
+            symbol: String,
+            decimals: u8,
+            collateral_address: AccountId,
+            collateral_price: u128,
+            price_decimals: u8,
+            collateral_ratio: u128,
+            max_supply: u128,
+            owners: Vec<AccountId>,
+            threshold: u8,
+        ) -> Self {
+            assert!(threshold > 0 && usize::from(threshold) <= owners.len(), "Invalid threshold");
+            assert!(
+                owners.iter().enumerate().all(|(index, owner)| !owners[..index].contains(owner)),
+                "Duplicate multisig owner"
+            );
+            let mut instance = Self::new(name, symbol, decimals, collateral_address, collateral_price, price_decimals, collateral_ratio, max_supply);
+            instance.multisig_owners = owners;
+            instance.threshold = threshold;
             instance
         }
 
@@ -70,246 +902,7242 @@ mod chest {
             self.decimals
         }
 
+        /// PSP22 `PSP22Metadata::token_name`, under its standard selector.
+        #[ink(message, selector = 0x3d261bd4)]
+        pub fn token_name(&self) -> Option<String> {
+            Some(self.name())
+        }
+
+        /// PSP22 `PSP22Metadata::token_symbol`, under its standard selector.
+        #[ink(message, selector = 0x34205be5)]
+        pub fn token_symbol(&self) -> Option<String> {
+            Some(self.symbol())
+        }
+
+        /// PSP22 `PSP22Metadata::token_decimals`, under its standard selector.
+        #[ink(message, selector = 0x7271b782)]
+        pub fn token_decimals(&self) -> u8 {
+            self.decimals()
+        }
+
+        /// Bitmask of the `CAPABILITY_*` features this contract supports.
         #[ink(message)]
-        pub fn total_supply(&self) -> u128 {
-            self.total_supply
+        pub fn capabilities(&self) -> u32 {
+            CAPABILITY_MINTABLE | CAPABILITY_BURNABLE | CAPABILITY_PAUSABLE | CAPABILITY_PERMIT
         }
 
         #[ink(message)]
-        pub fn balance_of(&self, owner: AccountId) -> u128 {
-            *self.balances.get(&owner).unwrap_or(&0)
+        pub fn price_decimals(&self) -> u8 {
+            self.price_decimals
         }
 
         #[ink(message)]
-        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> u128 {
-            *self.allowed.get(&(owner, spender)).unwrap_or(&0)
+        pub fn collateral_address(&self) -> AccountId {
+            self.collateral_address
         }
 
+        /// Last known collateral price, as cached on mint, redeem or `update_price`.
         #[ink(message)]
-        pub fn approve(&mut self, spender: AccountId, amount: u128) -> bool {
-            let sender = self.env().caller();
-            self.allowed.insert((sender, spender), amount);
-            self.env().emit_event(Approval {
-                owner: sender,
-                spender,
-                amount,
-            });
-            true
+        pub fn collateral_price(&self) -> u128 {
+            self.collateral_price
         }
 
+        /// Collateral address, cached price and the time that price was last
+        /// updated, in a single query.
         #[ink(message)]
-        pub fn transfer(&mut self, to: AccountId, amount: u128) -> bool {
-            let sender = self.env().caller();
-            self.transfer_from_to(sender, to, amount)
+        pub fn collateral_config(&self) -> (AccountId, u128, u64) {
+            (self.collateral_address, self.collateral_price, self.price_updated_at)
         }
 
         #[ink(message)]
-        pub fn transfer_from(&mut self, from: AccountId, to: AccountId, amount: u128) -> bool {
-            let sender = self.env().caller();
-            let allowance = self.allowed.get(&(from, sender)).cloned().unwrap_or(0);
-            assert!(allowance >= amount, "Not enough allowance");
+        pub fn price_updated_at(&self) -> u64 {
+            self.price_updated_at
+        }
 
-            self.allowed.insert((from, sender), allowance - amount);
-            self.transfer_from_to(from, to, amount)
+        #[ink(message)]
+        pub fn max_price_age(&self) -> u64 {
+            self.max_price_age
         }
 
-        fn transfer_from_to(&mut self, from: AccountId, to: AccountId, amount: u128) -> bool {
-            assert!(self.balances.contains_key(&from), "Sender does not have a balance");
-            let balance = self.balances.entry(from).or_insert(0);
-            assert!(*balance >= amount, "Sender does not have a balance");
-        
-            *balance -= amount;
-        
-            let to_balance = self.balances.entry(to).or_insert(0);
-            *to_balance += amount;
-        
-            self.env().emit_event(Transfer {
-                from,
-                to,
-                amount,
-            });
-            true
+        /// Lowest and highest collateral price accepted, inclusive.
+        #[ink(message)]
+        pub fn price_bounds(&self) -> (u128, u128) {
+            (self.min_price, self.max_price)
         }
-        
 
         #[ink(message)]
-        pub fn mint(&mut self, amount: u128) {
-            let sender = self.env().caller();
-            let collateral_amount = amount * self.collateral_price / 100; // Collateral amount calculated based on the price feed
+        pub fn collateral_ratio(&self) -> u128 {
+            self.collateral_ratio
+        }
 
-            self.collateral_pool += collateral_amount;
-            assert!(self.collateral_pool > 0, "Collateral pool should be greater than 0");
+        /// Maximum number of tokens that may ever be in circulation, `0` meaning unlimited.
+        #[ink(message)]
+        pub fn max_supply(&self) -> u128 {
+            self.max_supply
+        }
 
-            let balance = self.balances.entry(sender).or_insert(0);
-            *balance += amount;
-            self.total_supply += amount;
+        /// Share of every transfer, in basis points, routed to the fee collector.
+        #[ink(message)]
+        pub fn fee_bps(&self) -> u128 {
+            self.fee_bps
+        }
 
-            self.env().emit_event(Minted {
-                from: sender,
-                to: sender,
-                amount,
-            });
+        /// Transfer, flash mint and redeem fees taken so far; never decreases.
+        #[ink(message)]
+        pub fn total_fees_collected(&self) -> u128 {
+            self.total_fees_collected
         }
 
         #[ink(message)]
-        pub fn redeem(&mut self, amount: u128) {
-            let sender = self.env().caller();
+        pub fn fees_collected_by(&self, collector: AccountId) -> u128 {
+            *self.fees_collected.get(&collector).unwrap_or(&0)
+        }
 
-            let balance = self.balances.entry(sender).or_insert(0);
-            assert!(*balance >= amount, "Not enough balance to redeem");
+        /// Fees `collector` received since they were last swept.
+        #[ink(message)]
+        pub fn unswept_fees_of(&self, collector: AccountId) -> u128 {
+            *self.unswept_fees.get(&collector).unwrap_or(&0)
+        }
 
-            let collateral_amount = amount * self.collateral_price / 100; // Collateral amount calculated based on the price feed
+        /// Share of redeemed collateral, in basis points, retained by the pool.
+        #[ink(message)]
+        pub fn redeem_fee_bps(&self) -> u128 {
+            self.redeem_fee_bps
+        }
 
-            assert!(self.collateral_pool >= collateral_amount, "Not enough collateral in the pool");
+        #[ink(message)]
+        pub fn fee_collector(&self) -> AccountId {
+            self.fee_collector
+        }
 
-            *balance -= amount;
-            self.total_supply -= amount;
-            self.collateral_pool -= collateral_amount;
+        /// Value of all collateral pools at their live prices relative to the
+        /// supply they back, in basis points. `u128::MAX` while nothing is minted.
+        #[ink(message)]
+        pub fn collateralization_ratio(&self) -> u128 {
+            self.collateralization_ratio_with(self.collateral_pool, self.pool_backed_supply())
+        }
 
-            self.env().emit_event(Redeemed {
-                from: sender,
-                to: sender,
-                amount,
-            });
+        /// `collateralization_ratio` for a primary pool of `collateral_pool` backing
+        /// `total_supply` tokens.
+        fn collateralization_ratio_with(&self, collateral_pool: u128, total_supply: u128) -> u128 {
+            if total_supply == 0 {
+                return u128::MAX;
+            }
+            let mut value = U256::from(collateral_pool) * U256::from(self.fetch_price());
+            for (token, info) in self.collaterals.iter() {
+                let price = CollateralRef::from_account_id(*token).price().unwrap_or(info.price);
+                value = value + U256::from(info.pool) * U256::from(price);
+            }
+            let value = value * U256::from(BASIS_POINTS);
+            let ratio = value / (U256::from(total_supply) * U256::from(self.price_scale()));
+            u128::try_from(ratio).unwrap_or(u128::MAX)
         }
-    }
 
-    #[ink(event)]
-    pub struct Approval {
-        #[ink(topic)]
-        owner: AccountId,
-        #[ink(topic)]
-        spender: AccountId,
-        amount: u128,
-    }
+        #[ink(message)]
+        pub fn native_reserve(&self) -> Balance {
+            self.native_reserve
+        }
 
-    #[ink(event)]
-    pub struct Transfer {
-        #[ink(topic)]
-        from: AccountId,
-        #[ink(topic)]
-        to: AccountId,
-        amount: u128,
-    }
+        #[ink(message)]
+        pub fn debt_of(&self, account: AccountId) -> u128 {
+            *self.debt.get(&account).unwrap_or(&0)
+        }
 
-    #[ink(event)]
-    pub struct Minted {
-        #[ink(topic)]
-        from: AccountId,
-        #[ink(topic)]
-        to: AccountId,
-        amount: u128,
-    }
+        #[ink(message)]
+        pub fn position_collateral_of(&self, account: AccountId) -> u128 {
+            *self.position_collateral.get(&account).unwrap_or(&0)
+        }
 
-    #[ink(event)]
-    pub struct Redeemed {
-        #[ink(topic)]
-        from: AccountId,
-        #[ink(topic)]
-        to: AccountId,
-        amount: u128,
-    }
+        #[ink(message)]
+        pub fn position_min_ratio(&self) -> u128 {
+            self.position_min_ratio
+        }
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
+        /// Value of `account`'s position collateral at the live price relative to
+        /// its debt, in basis points. `u128::MAX` without debt.
+        #[ink(message)]
+        pub fn position_health(&self, account: AccountId) -> u128 {
+            self.health_of(self.position_collateral_of(account), self.debt_of(account), self.fetch_price())
+        }
 
-        #[test]
-        fn create_contract_works() {
-            let accounts =ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100);
-            assert_eq!(chest.name(), "Chest".to_string());
-            assert_eq!(chest.symbol(), "CHEST".to_string());
-            assert_eq!(chest.decimals(), 18);
-            assert_eq!(chest.total_supply(), 0);
+        /// Primary collateral `account` deposited through minting that it has not
+        /// redeemed yet.
+        #[ink(message)]
+        pub fn collateral_contributed_by(&self, account: AccountId) -> u128 {
+            *self.collateral_contributed.get(&account).unwrap_or(&0)
         }
 
-        #[test]
-        fn mint_works() {
-            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100);
-            let amount = 100_000;
-            chest.mint(amount);
-            assert_eq!(chest.total_supply(), amount);
-            assert_eq!(chest.balance_of(accounts.alice), amount);
+        /// Value of the primary collateral pool at the cached price.
+        #[ink(message)]
+        pub fn collateral_value(&self) -> u128 {
+            mul_div(self.collateral_pool, self.collateral_price, self.price_scale()).unwrap_or(u128::MAX)
         }
 
-        #[test]
-        fn redeem_works() {
+        /// Whether the caller could `mint` `amount` right now: the collateral it
+        /// would be charged, or why the mint would fail. Mirrors the checks in
+        /// `mint_against`, so the two have to be kept in sync.
+        #[ink(message)]
+        pub fn can_mint(&self, amount: u128) -> Result<u128> {
+            let caller = self.env().caller();
+            if !self.has_role(MINTER_ROLE, caller) {
+                return Err(Error::MissingRole);
+            }
+            if amount < self.min_mint_amount {
+                return Err(Error::BelowMinimumMint);
+            }
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            let new_supply = self.total_supply.checked_add(amount).ok_or(Error::ArithmeticOverflow)?;
+            if self.max_supply != 0 && new_supply > self.max_supply {
+                return Err(Error::MaxSupplyExceeded);
+            }
+            let minted_in_block = self.minted_in_current_block().checked_add(amount).ok_or(Error::ArithmeticOverflow)?;
+            if self.max_mint_per_block != 0 && minted_in_block > self.max_mint_per_block {
+                return Err(Error::MintRateLimitExceeded);
+            }
+            let now = self.env().block_timestamp();
+            if now < self.next_mint_time(caller) {
+                return Err(Error::MintCooldownActive);
+            }
+
+            let price = self.live_price();
+            if price < self.min_price || price > self.max_price {
+                return Err(Error::PriceOutOfBounds);
+            }
+            // A changed price counts as fresh, as `refresh_price` would record it
+            let updated_at = if price != self.collateral_price { now } else { self.price_updated_at };
+            if self.max_price_age != 0 && now.saturating_sub(updated_at) > self.max_price_age {
+                return Err(Error::StalePrice);
+            }
+            let collateral_amount = self.collateral_for(amount, price, self.mint_rounding).ok_or(Error::ArithmeticOverflow)?;
+            if collateral_amount > self.collateral_headroom() {
+                return Err(Error::CollateralCapReached);
+            }
+            let pool = self.collateral_pool.checked_add(collateral_amount).ok_or(Error::ArithmeticOverflow)?;
+            let backed_supply = self.pool_backed_supply().checked_add(amount).ok_or(Error::ArithmeticOverflow)?;
+            if pool == 0 || self.collateralization_ratio_with(pool, backed_supply) < self.min_collateral_ratio {
+                return Err(Error::InsufficientCollateral);
+            }
+            Ok(collateral_amount)
+        }
+
+        /// Most tokens `collateral_balance` of the primary collateral can mint at
+        /// the live price, within the remaining `max_supply` headroom. `0` if that
+        /// is below `min_mint_amount`.
+        #[ink(message)]
+        pub fn max_mintable_for(&self, collateral_balance: u128) -> u128 {
+            let denominator = U256::from(self.fetch_price()) * U256::from(self.collateral_ratio);
+            let mintable = (U256::from(collateral_balance) * U256::from(BASIS_POINTS) * U256::from(self.price_scale()))
+                .checked_div(denominator)
+                .map_or(u128::MAX, |mintable| u128::try_from(mintable).unwrap_or(u128::MAX));
+            let mintable = if self.max_supply == 0 {
+                mintable
+            } else {
+                mintable.min(self.max_supply.saturating_sub(self.total_supply))
+            };
+            if mintable < self.min_mint_amount {
+                0
+            } else {
+                mintable
+            }
+        }
+
+        /// `collateral_value` relative to the supply the pools back, in basis
+        /// points. `u128::MAX` while nothing is minted.
+        #[ink(message)]
+        pub fn backing_ratio_bps(&self) -> u128 {
+            self.backing_ratio_at(self.collateral_price)
+        }
+
+        /// `backing_ratio_bps` with the primary collateral valued at `price`.
+        fn backing_ratio_at(&self, price: u128) -> u128 {
+            let backed_supply = self.pool_backed_supply();
+            if backed_supply == 0 {
+                return u128::MAX;
+            }
+            let value = mul_div(self.collateral_pool, price, self.price_scale()).unwrap_or(u128::MAX);
+            mul_div(value, BASIS_POINTS, backed_supply).unwrap_or(u128::MAX)
+        }
+
+        /// Collateral of type `token` held by this contract, `0` for unknown types.
+        #[ink(message)]
+        pub fn collateral_pool_of(&self, token: AccountId) -> u128 {
+            if token == self.collateral_address {
+                self.collateral_pool
+            } else {
+                self.collaterals.get(&token).map_or(0, |info| info.pool)
+            }
+        }
+
+        /// Collateral in the pool beyond what backs the total supply at the live price
+        /// and the `reserve_bps` buffer.
+        #[ink(message)]
+        pub fn excess_collateral(&self) -> u128 {
+            let reserve = mul_div(self.collateral_pool, self.reserve_bps, BASIS_POINTS).unwrap_or(u128::MAX);
+            let backing = self
+                .collateral_for(self.total_supply, self.fetch_price(), RoundingMode::Down)
+                .unwrap_or(u128::MAX)
+                .saturating_add(self.redemption_queued)
+                .saturating_add(reserve);
+            self.collateral_pool.saturating_sub(backing)
+        }
+
+        #[ink(message)]
+        pub fn reserve_bps(&self) -> u128 {
+            self.reserve_bps
+        }
+
+        #[ink(message)]
+        pub fn liquidation_ratio(&self) -> u128 {
+            self.liquidation_ratio
+        }
+
+        #[ink(message)]
+        pub fn liquidation_bonus(&self) -> u128 {
+            self.liquidation_bonus
+        }
+
+        #[ink(message)]
+        pub fn min_collateral_ratio(&self) -> u128 {
+            self.min_collateral_ratio
+        }
+
+        #[ink(message)]
+        pub fn has_role(&self, role: RoleId, account: AccountId) -> bool {
+            *self.roles.get(&(role, account)).unwrap_or(&false)
+        }
+
+        #[ink(message)]
+        pub fn max_mint_per_block(&self) -> u128 {
+            self.max_mint_per_block
+        }
+
+        #[ink(message)]
+        pub fn max_redeem_per_block(&self) -> u128 {
+            self.max_redeem_per_block
+        }
+
+        #[ink(message)]
+        pub fn min_mint_amount(&self) -> u128 {
+            self.min_mint_amount
+        }
+
+        #[ink(message)]
+        pub fn mint_cooldown(&self) -> u64 {
+            self.mint_cooldown
+        }
+
+        /// Earliest timestamp at which `account` can be minted to again.
+        #[ink(message)]
+        pub fn next_mint_time(&self, account: AccountId) -> u64 {
+            match self.last_mint_at.get(&account) {
+                Some(last) if self.mint_cooldown > 0 => last.saturating_add(self.mint_cooldown),
+                _ => 0,
+            }
+        }
+
+        /// Tokens minted so far in the current block.
+        #[ink(message)]
+        pub fn minted_in_current_block(&self) -> u128 {
+            if self.mint_block == self.env().block_number() {
+                self.minted_in_block
+            } else {
+                0
+            }
+        }
+
+        #[ink(message)]
+        pub fn mint_allowance_of(&self, minter: AccountId) -> u128 {
+            *self.mint_allowance.get(&minter).unwrap_or(&0)
+        }
+
+        /// Collateral escrowed for `account` by delayed redeems and the timestamp it
+        /// can be claimed from.
+        #[ink(message)]
+        pub fn pending_collateral_of(&self, account: AccountId) -> (u128, u64) {
+            *self.pending_collateral.get(&account).unwrap_or(&(0, 0))
+        }
+
+        #[ink(message)]
+        pub fn redeem_delay(&self) -> u64 {
+            self.redeem_delay
+        }
+
+        /// Collateral queued for `account` by `request_redeem` and not paid out yet.
+        #[ink(message)]
+        pub fn pending_redemption_of(&self, account: AccountId) -> u128 {
+            *self.pending_redemptions.get(&account).unwrap_or(&0)
+        }
+
+        /// Tokens redeemed so far in the current block.
+        #[ink(message)]
+        pub fn redeemed_in_current_block(&self) -> u128 {
+            if self.redeem_block == self.env().block_number() {
+                self.redeemed_in_block
+            } else {
+                0
+            }
+        }
+
+        #[ink(message)]
+        pub fn flash_fee_bps(&self) -> u128 {
+            self.flash_fee_bps
+        }
+
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        #[ink(message)]
+        pub fn pending_owner(&self) -> Option<AccountId> {
+            self.pending_owner
+        }
+
+        #[ink(message)]
+        pub fn paused(&self) -> bool {
+            self.paused
+        }
+
+        #[ink(message)]
+        pub fn max_tx_amount(&self) -> u128 {
+            self.max_tx_amount
+        }
+
+        #[ink(message)]
+        pub fn insolvency_threshold(&self) -> u128 {
+            self.insolvency_threshold
+        }
+
+        #[ink(message)]
+        pub fn is_frozen(&self, account: AccountId) -> bool {
+            *self.frozen.get(&account).unwrap_or(&false)
+        }
+
+        #[ink(message)]
+        pub fn max_collateral_pool(&self) -> u128 {
+            self.max_collateral_pool
+        }
+
+        /// Primary collateral that can still be deposited before the cap is reached,
+        /// `u128::MAX` without a cap.
+        #[ink(message)]
+        pub fn collateral_headroom(&self) -> u128 {
+            if self.max_collateral_pool == 0 {
+                return u128::MAX;
+            }
+            self.max_collateral_pool.saturating_sub(self.collateral_pool)
+        }
+
+        /// The primary collateral the contract owes, i.e. the pool (queued
+        /// redemptions included), position collateral, escrowed redemptions and
+        /// unclaimed dividends, next to the collateral it actually holds. A
+        /// mismatch points to unaccounted transfers.
+        #[ink(message)]
+        pub fn collateral_reconciliation(&self) -> (u128, u128) {
+            let accounted = self
+                .collateral_pool
+                .saturating_add(self.total_position_collateral)
+                .saturating_add(self.total_escrowed)
+                .saturating_add(self.dividend_reserve);
+            let held = CollateralRef::from_account_id(self.collateral_address).balance_of(self.env().account_id());
+            (accounted, held)
+        }
+
+        #[ink(message)]
+        pub fn collateral_floor(&self) -> u128 {
+            self.collateral_floor
+        }
+
+        #[ink(message)]
+        pub fn is_fee_exempt(&self, account: AccountId) -> bool {
+            *self.fee_exempt.get(&account).unwrap_or(&false)
+        }
+
+        #[ink(message)]
+        pub fn whitelist_enabled(&self) -> bool {
+            self.whitelist_enabled
+        }
+
+        #[ink(message)]
+        pub fn is_whitelisted(&self, account: AccountId) -> bool {
+            *self.transfer_whitelist.get(&account).unwrap_or(&false)
+        }
+
+        #[ink(message)]
+        pub fn total_supply(&self) -> u128 {
+            self.total_supply
+        }
+
+        /// `total_supply` less the balances of the non-circulating accounts.
+        #[ink(message)]
+        pub fn circulating_supply(&self) -> u128 {
+            self.non_circulating
+                .iter()
+                .fold(self.total_supply, |supply, account| supply.saturating_sub(self.balance_of(*account)))
+        }
+
+        #[ink(message)]
+        pub fn non_circulating_accounts(&self) -> Vec<AccountId> {
+            self.non_circulating.clone()
+        }
+
+        /// Tokens minted over the contract's lifetime; never decreases.
+        #[ink(message)]
+        pub fn total_minted(&self) -> u128 {
+            self.total_minted
+        }
+
+        /// Tokens redeemed over the contract's lifetime; never decreases.
+        #[ink(message)]
+        pub fn total_redeemed(&self) -> u128 {
+            self.total_redeemed
+        }
+
+        #[ink(message)]
+        pub fn balance_of(&self, owner: AccountId) -> u128 {
+            let raw = *self.balances.get(&owner).unwrap_or(&0);
+            mul_div(raw, self.scaling_factor, SCALING_UNIT).unwrap_or(u128::MAX)
+        }
+
+        /// Current rebase multiplier, where `1_000_000_000_000_000_000` is `1.0`.
+        #[ink(message)]
+        pub fn scaling_factor(&self) -> u128 {
+            self.scaling_factor
+        }
+
+        /// Number of accounts currently holding tokens.
+        #[ink(message)]
+        pub fn holders_count(&self) -> u32 {
+            self.holders.len()
+        }
+
+        /// Up to `len` holders starting at position `start`. Positions shift as
+        /// accounts drop to a zero balance.
+        #[ink(message)]
+        pub fn holders_page(&self, start: u32, len: u32) -> Vec<AccountId> {
+            self.holders
+                .iter()
+                .skip(start as usize)
+                .take(len as usize)
+                .cloned()
+                .collect()
+        }
+
+        #[ink(message)]
+        pub fn current_snapshot_id(&self) -> u128 {
+            self.current_snapshot_id
+        }
+
+        #[ink(message)]
+        pub fn balance_of_at(&self, account: AccountId, snapshot_id: u128) -> u128 {
+            self.assert_valid_snapshot(snapshot_id);
+            self.account_snapshots
+                .get(&account)
+                .and_then(|snapshots| Self::value_at(snapshots, snapshot_id))
+                .unwrap_or_else(|| self.balance_of(account))
+        }
+
+        #[ink(message)]
+        pub fn total_supply_at(&self, snapshot_id: u128) -> u128 {
+            self.assert_valid_snapshot(snapshot_id);
+            Self::value_at(&self.total_supply_snapshots, snapshot_id).unwrap_or(self.total_supply)
+        }
+
+        #[ink(message)]
+        pub fn staked_of(&self, account: AccountId) -> u128 {
+            *self.staked.get(&account).unwrap_or(&0)
+        }
+
+        #[ink(message)]
+        pub fn reward_rate(&self) -> u128 {
+            self.reward_rate
+        }
+
+        /// Staking rewards `account` could claim right now.
+        #[ink(message)]
+        pub fn pending_rewards(&self, account: AccountId) -> u128 {
+            let unclaimed = *self.unclaimed_rewards.get(&account).unwrap_or(&0);
+            let since = *self.stake_blocks.get(&account).unwrap_or(&self.env().block_number());
+            let blocks = u128::from(self.env().block_number().saturating_sub(since));
+            let accrued = U256::from(self.staked_of(account)) * U256::from(self.reward_rate) * U256::from(blocks)
+                / U256::from(BASIS_POINTS);
+            let accrued = u128::try_from(accrued).unwrap_or(u128::MAX);
+            unclaimed.saturating_add(accrued)
+        }
+
+        /// Dividends, in collateral, `account` could claim right now.
+        #[ink(message)]
+        pub fn claimable_dividend(&self, account: AccountId) -> u128 {
+            let owed = *self.dividends_owed.get(&account).unwrap_or(&0);
+            let debt = *self.reward_debt.get(&account).unwrap_or(&0);
+            let accrued = mul_div(self.balance_of(account), self.reward_per_token - debt, DIVIDEND_PRECISION)
+                .unwrap_or(u128::MAX);
+            owed.saturating_add(accrued)
+        }
+
+        /// Part of `beneficiary`'s vesting schedule unlocked so far, including what
+        /// was already released.
+        #[ink(message)]
+        pub fn vested_amount(&self, beneficiary: AccountId) -> u128 {
+            let schedule = match self.vesting.get(&beneficiary) {
+                Some(schedule) => schedule,
+                None => return 0,
+            };
+            let now = self.env().block_timestamp();
+            if now < schedule.start {
+                return 0;
+            }
+            let elapsed = now - schedule.start;
+            if elapsed >= schedule.duration {
+                return schedule.total;
+            }
+            mul_div(schedule.total, u128::from(elapsed), u128::from(schedule.duration)).unwrap_or(schedule.total)
+        }
+
+        /// Vested tokens `beneficiary` has not released yet.
+        #[ink(message)]
+        pub fn releasable(&self, beneficiary: AccountId) -> u128 {
+            let released = self.vesting.get(&beneficiary).map_or(0, |schedule| schedule.released);
+            self.vested_amount(beneficiary) - released
+        }
+
+        #[ink(message)]
+        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> u128 {
+            *self.allowed.get(&(owner, spender)).unwrap_or(&0)
+        }
+
+        /// Every spender `owner` has a non-zero allowance for, with the allowance.
+        #[ink(message)]
+        pub fn allowances_of(&self, owner: AccountId) -> Vec<(AccountId, u128)> {
+            self.spenders
+                .get(&owner)
+                .map(|spenders| {
+                    spenders
+                        .iter()
+                        .map(|spender| (*spender, self.allowance(owner, *spender)))
+                        .filter(|(_, allowance)| *allowance > 0)
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn compliance_contract(&self) -> Option<AccountId> {
+            self.compliance_contract
+        }
+
+        #[ink(message)]
+        pub fn allow_zero_transfers(&self) -> bool {
+            self.allow_zero_transfers
+        }
+
+        #[ink(message)]
+        pub fn yield_source(&self) -> Option<AccountId> {
+            self.yield_source
+        }
+
+        #[ink(message)]
+        pub fn total_yield_accrued(&self) -> u128 {
+            self.total_yield_accrued
+        }
+
+        /// Rounding applied on mint and on redeem.
+        #[ink(message)]
+        pub fn rounding_modes(&self) -> (RoundingMode, RoundingMode) {
+            (self.mint_rounding, self.redeem_rounding)
+        }
+
+        /// Transfer cap and the window length it applies to.
+        #[ink(message)]
+        pub fn velocity_limit(&self) -> (u128, u64) {
+            (self.max_transfer_per_window, self.velocity_window)
+        }
+
+        /// How much more `account` can send in its current window.
+        #[ink(message)]
+        pub fn remaining_transfer_allowance(&self, account: AccountId) -> u128 {
+            if self.max_transfer_per_window == 0 {
+                return u128::MAX;
+            }
+            self.max_transfer_per_window.saturating_sub(self.window_volume(account))
+        }
+
+        #[ink(message)]
+        pub fn dust_threshold(&self) -> u128 {
+            self.dust_threshold
+        }
+
+        #[ink(message)]
+        pub fn strict_approve(&self) -> bool {
+            self.strict_approve
+        }
+
+        /// Timestamp after which `spender`'s allowance over `owner`'s tokens expires,
+        /// `u64::MAX` if it never does.
+        #[ink(message)]
+        pub fn allowance_expiry(&self, owner: AccountId, spender: AccountId) -> u64 {
+            *self.allowance_expiries.get(&(owner, spender)).unwrap_or(&u64::MAX)
+        }
+
+        /// Whether `spender` could make `transfer_from` calls of `amounts` out of
+        /// `from` right now, and the allowance that would be left afterwards.
+        #[ink(message)]
+        pub fn batch_transfer_from_preview(&self, from: AccountId, spender: AccountId, amounts: Vec<u128>) -> (bool, u128) {
+            let allowance = self.allowance(from, spender);
+            let total = match amounts.iter().try_fold(0u128, |total, amount| total.checked_add(*amount)) {
+                Some(total) => total,
+                None => return (false, allowance),
+            };
+            let live = self.env().block_timestamp() <= self.allowance_expiry(from, spender);
+            let feasible = live && total <= allowance && total <= self.balance_of(from);
+            (feasible, allowance.saturating_sub(total))
+        }
+
+        /// `owner`'s balance, `spender`'s allowance over it and whether `owner` is
+        /// frozen, in a single query.
+        #[ink(message)]
+        pub fn account_info(&self, owner: AccountId, spender: AccountId) -> (u128, u128, bool) {
+            (self.balance_of(owner), self.allowance(owner, spender), self.is_frozen(owner))
+        }
+
+        #[ink(message)]
+        pub fn approve(&mut self, spender: AccountId, amount: u128) -> bool {
+            assert!(!Self::is_zero_address(&spender), "Zero address not allowed");
+            let sender = self.env().caller();
+            assert!(
+                !self.strict_approve || amount == 0 || self.allowance(sender, spender) == 0,
+                "Must reset allowance to zero first"
+            );
+            self.set_allowance(sender, spender, amount);
+            self.allowance_expiries.take(&(sender, spender));
+            self.env().emit_event(Approval {
+                owner: sender,
+                spender,
+                amount,
+            });
+            true
+        }
+
+        /// Same as `approve`, but the allowance can't be spent after `expiry`.
+        #[ink(message)]
+        pub fn approve_with_expiry(&mut self, spender: AccountId, amount: u128, expiry: u64) -> bool {
+            self.approve(spender, amount);
+            let sender = self.env().caller();
+            self.allowance_expiries.insert((sender, spender), expiry);
+            true
+        }
+
+        /// Number of permits `owner` has signed so far, which must be included in
+        /// the next permit message.
+        #[ink(message)]
+        pub fn nonces(&self, owner: AccountId) -> u128 {
+            *self.nonces.get(&owner).unwrap_or(&0)
+        }
+
+        /// Sets the allowance of `spender` over `owner`'s tokens from an ECDSA
+        /// signature by `owner`.
+        ///
+        /// The signed message is the `Blake2x256` hash of the SCALE encoded tuple
+        /// `(contract, owner, spender, value, nonce, deadline)`, and `owner` must be
+        /// the `Blake2x256` hash of the signer's compressed public key.
+        #[ink(message)]
+        pub fn permit(&mut self, owner: AccountId, spender: AccountId, value: u128, deadline: u64, signature: [u8; 65]) {
+            assert!(!Self::is_zero_address(&spender), "Zero address not allowed");
+            assert!(self.env().block_timestamp() <= deadline, "Permit expired");
+
+            let nonce = self.nonces(owner);
+            let message = self.env().hash_encoded::<Blake2x256, _>(&(
+                self.env().account_id(),
+                owner,
+                spender,
+                value,
+                nonce,
+                deadline,
+            ));
+            let mut public_key = [0u8; 33];
+            self.env()
+                .ecdsa_recover(&signature, &message, &mut public_key)
+                .expect("Invalid signature");
+            let signer = AccountId::from(self.env().hash_bytes::<Blake2x256>(&public_key));
+            assert!(signer == owner, "Invalid signature");
+
+            self.nonces.insert(owner, nonce + 1);
+            self.set_allowance(owner, spender, value);
+            self.allowance_expiries.take(&(owner, spender));
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                amount: value,
+            });
+        }
+
+        /// Zeroes every allowance the caller has granted.
+        #[ink(message)]
+        pub fn revoke_all(&mut self) {
+            let sender = self.env().caller();
+            for spender in self.spenders.take(&sender).unwrap_or_default() {
+                self.allowed.insert((sender, spender), 0);
+                self.allowance_expiries.take(&(sender, spender));
+                self.env().emit_event(Approval {
+                    owner: sender,
+                    spender,
+                    amount: 0,
+                });
+            }
+        }
+
+        #[ink(message)]
+        pub fn increase_allowance(&mut self, spender: AccountId, delta: u128) -> bool {
+            assert!(!Self::is_zero_address(&spender), "Zero address not allowed");
+            let sender = self.env().caller();
+            let amount = self.allowance(sender, spender).saturating_add(delta);
+            self.set_allowance(sender, spender, amount);
+            self.env().emit_event(Approval {
+                owner: sender,
+                spender,
+                amount,
+            });
+            true
+        }
+
+        #[ink(message)]
+        pub fn decrease_allowance(&mut self, spender: AccountId, delta: u128) -> bool {
+            let sender = self.env().caller();
+            let amount = self.allowance(sender, spender).saturating_sub(delta);
+            self.set_allowance(sender, spender, amount);
+            self.env().emit_event(Approval {
+                owner: sender,
+                spender,
+                amount,
+            });
+            true
+        }
+
+        #[ink(message)]
+        pub fn transfer(&mut self, to: AccountId, amount: u128) -> Result<()> {
+            let sender = self.env().caller();
+            let balance = self.balance_of(sender);
+            let remainder = balance.saturating_sub(amount);
+            let amount = if remainder > 0 && remainder < self.dust_threshold {
+                balance
+            } else {
+                amount
+            };
+            self.transfer_from_to(sender, to, amount)
+        }
+
+        /// Transfers `whole` tokens plus `fractional` base units, scaling by
+        /// `decimals` on the caller's behalf. Zero-decimal tokens have no
+        /// fractional units, so `fractional` must be `0`.
+        #[ink(message)]
+        pub fn transfer_units(&mut self, to: AccountId, whole: u128, fractional: u128) -> bool {
+            let unit = 10u128.checked_pow(u32::from(self.decimals)).expect("Arithmetic overflow in transfer");
+            assert!(fractional < unit, "Fractional part too large");
+            let amount = whole
+                .checked_mul(unit)
+                .and_then(|amount| amount.checked_add(fractional))
+                .expect("Arithmetic overflow in transfer");
+            self.transfer(to, amount).is_ok()
+        }
+
+        /// PSP22 `PSP22::total_supply`, under its standard selector.
+        #[ink(message, selector = 0x162df8c2)]
+        pub fn psp22_total_supply(&self) -> u128 {
+            self.total_supply()
+        }
+
+        /// PSP22 `PSP22::balance_of`, under its standard selector.
+        #[ink(message, selector = 0x6568382f)]
+        pub fn psp22_balance_of(&self, owner: AccountId) -> u128 {
+            self.balance_of(owner)
+        }
+
+        /// PSP22 `PSP22::allowance`, under its standard selector.
+        #[ink(message, selector = 0x4d47d921)]
+        pub fn psp22_allowance(&self, owner: AccountId, spender: AccountId) -> u128 {
+            self.allowance(owner, spender)
+        }
+
+        /// PSP22 `PSP22::transfer`, under its standard selector. `data` is ignored.
+        #[ink(message, selector = 0xdb20f9f5)]
+        pub fn psp22_transfer(&mut self, to: AccountId, value: u128, _data: Vec<u8>) -> PSP22Result<()> {
+            Ok(self.transfer(to, value)?)
+        }
+
+        /// PSP22 `PSP22::transfer_from`, under its standard selector. `data` is ignored.
+        #[ink(message, selector = 0x54b3c76e)]
+        pub fn psp22_transfer_from(&mut self, from: AccountId, to: AccountId, value: u128, _data: Vec<u8>) -> PSP22Result<()> {
+            Ok(self.transfer_from(from, to, value)?)
+        }
+
+        /// PSP22 `PSP22::approve`, under its standard selector.
+        #[ink(message, selector = 0xb20f1bbd)]
+        pub fn psp22_approve(&mut self, spender: AccountId, value: u128) -> PSP22Result<()> {
+            if Self::is_zero_address(&spender) {
+                return Err(PSP22Error::ZeroRecipientAddress);
+            }
+            self.approve(spender, value);
+            Ok(())
+        }
+
+        /// PSP22 `PSP22::increase_allowance`, under its standard selector.
+        #[ink(message, selector = 0x96d6b57a)]
+        pub fn psp22_increase_allowance(&mut self, spender: AccountId, delta_value: u128) -> PSP22Result<()> {
+            if Self::is_zero_address(&spender) {
+                return Err(PSP22Error::ZeroRecipientAddress);
+            }
+            self.increase_allowance(spender, delta_value);
+            Ok(())
+        }
+
+        /// PSP22 `PSP22::decrease_allowance`, under its standard selector. Unlike
+        /// `decrease_allowance`, fails instead of stopping at zero.
+        #[ink(message, selector = 0xfecb57d5)]
+        pub fn psp22_decrease_allowance(&mut self, spender: AccountId, delta_value: u128) -> PSP22Result<()> {
+            if Self::is_zero_address(&spender) {
+                return Err(PSP22Error::ZeroRecipientAddress);
+            }
+            let owner = self.env().caller();
+            if self.allowance(owner, spender) < delta_value {
+                return Err(PSP22Error::InsufficientAllowance);
+            }
+            self.decrease_allowance(spender, delta_value);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn transfer_from(&mut self, from: AccountId, to: AccountId, amount: u128) -> Result<()> {
+            let sender = self.env().caller();
+            // Checked up front, spent once the transfer went through
+            self.check_allowance(from, sender, amount)?;
+            self.transfer_from_to(from, to, amount)?;
+            self.spend_allowance(from, sender, amount)
+        }
+
+        #[ink(message)]
+        pub fn batch_transfer(&mut self, recipients: Vec<AccountId>, amounts: Vec<u128>) -> bool {
+            assert!(recipients.len() == amounts.len(), "Recipients and amounts length mismatch");
+            let sender = self.env().caller();
+            let total = amounts
+                .iter()
+                .try_fold(0u128, |total, amount| total.checked_add(*amount))
+                .expect("Arithmetic overflow in batch transfer");
+            assert!(self.balance_of(sender) >= total, "Not enough balance");
+
+            // Any failure has to trap so the transfers already made are rolled back
+            for (to, amount) in recipients.into_iter().zip(amounts) {
+                self.transfer_from_to(sender, to, amount).expect("Batch transfer failed");
+            }
+            true
+        }
+
+        /// Same as `transfer`, but emits the caller-supplied `memo` as the topic of the
+        /// `Transfer` event.
+        #[ink(message)]
+        pub fn transfer_with_memo(&mut self, to: AccountId, amount: u128, memo: [u8; 32]) -> Result<()> {
+            let sender = self.env().caller();
+            self.transfer_from_to_with_memo(sender, to, amount, Hash::from(memo))
+        }
+
+        /// Same as `transfer`, but if `to` is a contract it must accept the tokens
+        /// through its `on_received` hook, otherwise the whole transfer traps.
+        #[ink(message)]
+        pub fn safe_transfer(&mut self, to: AccountId, amount: u128, data: Vec<u8>) -> Result<()> {
+            let sender = self.env().caller();
+            self.transfer_from_to(sender, to, amount)?;
+            if self.is_contract(to) {
+                assert!(
+                    TokenRecipientRef::from_account_id(to).on_received(sender, amount, data),
+                    "Recipient rejected transfer"
+                );
+            }
+            Ok(())
+        }
+
+        fn transfer_from_to(&mut self, from: AccountId, to: AccountId, amount: u128) -> Result<()> {
+            let memo = self.transfer_memo();
+            self.transfer_from_to_with_memo(from, to, amount, memo)
+        }
+
+        /// What `account` has sent in its current window, `0` once it has rolled over.
+        fn window_volume(&self, account: AccountId) -> u128 {
+            match self.transfer_windows.get(&account) {
+                Some((start, volume)) if self.env().block_timestamp() < start.saturating_add(self.velocity_window) => *volume,
+                _ => 0,
+            }
+        }
+
+        fn transfer_from_to_with_memo(&mut self, from: AccountId, to: AccountId, amount: u128, memo: Hash) -> Result<()> {
+            if Self::is_zero_address(&to) {
+                return Err(Error::ZeroAddress);
+            }
+            if !self.allow_zero_transfers && amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            if self.paused {
+                return Err(Error::ContractPaused);
+            }
+            if self.is_frozen(from) || self.is_frozen(to) {
+                return Err(Error::AccountFrozen);
+            }
+            if self.whitelist_enabled && !(self.is_whitelisted(from) && self.is_whitelisted(to)) {
+                return Err(Error::NotWhitelisted);
+            }
+            if self.max_tx_amount != 0 && amount > self.max_tx_amount && !(self.owner_exempt_from_max_tx && from == self.owner) {
+                return Err(Error::MaxTxAmountExceeded);
+            }
+            if let Some(compliance) = self.compliance_contract {
+                if !ComplianceRef::from_account_id(compliance).can_transfer(from, to, amount) {
+                    return Err(Error::TransferNotCompliant);
+                }
+            }
+
+            let balance = self.balance_of(from);
+            if balance < amount {
+                return Err(Error::InsufficientBalance);
+            }
+            if from == to {
+                return Ok(());
+            }
+            if self.max_transfer_per_window > 0 {
+                let volume = self.window_volume(from).checked_add(amount).ok_or(Error::ArithmeticOverflow)?;
+                if volume > self.max_transfer_per_window {
+                    return Err(Error::TransferVelocityExceeded);
+                }
+                let now = self.env().block_timestamp();
+                let start = match self.transfer_windows.get(&from) {
+                    Some((start, _)) if now < start.saturating_add(self.velocity_window) => *start,
+                    _ => now,
+                };
+                self.transfer_windows.insert(from, (start, volume));
+            }
+
+            // Rounds down, so dust transfers stay fee-free
+            let fee = if self.is_fee_exempt(from) || self.is_fee_exempt(to) {
+                0
+            } else {
+                mul_div(amount, self.fee_bps, BASIS_POINTS).expect("Arithmetic overflow in transfer")
+            };
+            let received = amount - fee;
+
+            self.set_balance(from, balance - amount);
+            let to_balance = self.balance_of(to).checked_add(received).expect("Arithmetic overflow in transfer");
+            self.set_balance(to, to_balance);
+
+            if fee > 0 {
+                let collector = self.fee_collector;
+                let collector_balance = self.balance_of(collector).checked_add(fee).expect("Arithmetic overflow in transfer");
+                self.set_balance(collector, collector_balance);
+                self.record_fee(collector, fee);
+                self.env().emit_event(FeeCollected {
+                    from,
+                    collector,
+                    amount: fee,
+                });
+            }
+
+            self.env().emit_event(Transfer {
+                from,
+                to,
+                amount: received,
+                memo,
+            });
+            Ok(())
+        }
+
+        /// Moves `amount` of the caller's tokens into escrow, releasing them to
+        /// `beneficiary` linearly from `start` over `duration` milliseconds.
+        #[ink(message)]
+        pub fn create_vesting(&mut self, beneficiary: AccountId, amount: u128, start: u64, duration: u64) {
+            assert!(!self.paused, "Contract is paused");
+            let sender = self.env().caller();
+            assert!(!self.is_frozen(sender) && !self.is_frozen(beneficiary), "Account is frozen");
+            assert!(
+                self.vesting.get(&beneficiary).map_or(true, |schedule| schedule.released == schedule.total),
+                "Vesting schedule already exists"
+            );
+
+            let balance = self.balance_of(sender);
+            assert!(balance >= amount, "Not enough balance to vest");
+            self.set_balance(sender, balance - amount);
+            self.vesting.insert(
+                beneficiary,
+                VestingSchedule {
+                    total: amount,
+                    released: 0,
+                    start,
+                    duration,
+                },
+            );
+
+            self.env().emit_event(VestingCreated {
+                beneficiary,
+                amount,
+                start,
+                duration,
+            });
+        }
+
+        #[ink(message)]
+        pub fn release_vested(&mut self) {
+            let beneficiary = self.env().caller();
+            let amount = self.releasable(beneficiary);
+            if amount == 0 {
+                return;
+            }
+
+            if let Some(schedule) = self.vesting.get_mut(&beneficiary) {
+                schedule.released += amount;
+            }
+            let balance = self.balance_of(beneficiary).checked_add(amount).expect("Arithmetic overflow in release");
+            self.set_balance(beneficiary, balance);
+
+            self.env().emit_event(VestingReleased {
+                beneficiary,
+                amount,
+            });
+        }
+
+        /// Takes `amount` of collateral from the caller and shares it among holders
+        /// pro rata to their current balances. Staked and vesting tokens are not
+        /// held by anyone who could claim, so they get no share.
+        #[ink(message)]
+        pub fn distribute(&mut self, amount: u128) {
+            let claimable_supply =
+                mul_div(self.held_raw_supply, self.scaling_factor, SCALING_UNIT).expect("Arithmetic overflow in distribute");
+            assert!(claimable_supply > 0, "No holders to distribute to");
+            let sender = self.env().caller();
+            let increase = mul_div(amount, DIVIDEND_PRECISION, claimable_supply).expect("Arithmetic overflow in distribute");
+            self.reward_per_token = self.reward_per_token.checked_add(increase).expect("Arithmetic overflow in distribute");
+            self.dividend_reserve = self.dividend_reserve.checked_add(amount).expect("Arithmetic overflow in distribute");
+            assert!(
+                CollateralRef::from_account_id(self.collateral_address).transfer_from(sender, self.env().account_id(), amount),
+                "Collateral transfer failed"
+            );
+
+            self.env().emit_event(DividendDistributed {
+                from: sender,
+                amount,
+            });
+        }
+
+        #[ink(message)]
+        pub fn claim_dividend(&mut self) {
+            let sender = self.env().caller();
+            self.settle_dividend(sender);
+            let amount = self.dividends_owed.take(&sender).unwrap_or(0);
+            if amount == 0 {
+                return;
+            }
+            self.dividend_reserve -= amount;
+            assert!(
+                CollateralRef::from_account_id(self.collateral_address).transfer(sender, amount),
+                "Collateral transfer failed"
+            );
+
+            self.env().emit_event(DividendClaimed {
+                account: sender,
+                amount,
+            });
+        }
+
+        /// Locks `amount` of the caller's tokens to accrue staking rewards.
+        #[ink(message)]
+        pub fn stake(&mut self, amount: u128) {
+            assert!(!self.paused, "Contract is paused");
+            let sender = self.env().caller();
+            assert!(!self.is_frozen(sender), "Account is frozen");
+            let balance = self.balance_of(sender);
+            assert!(balance >= amount, "Not enough balance to stake");
+
+            self.settle_rewards(sender);
+            self.set_balance(sender, balance - amount);
+            let staked = self.staked_of(sender).checked_add(amount).expect("Arithmetic overflow in stake");
+            self.staked.insert(sender, staked);
+
+            self.env().emit_event(Staked {
+                account: sender,
+                amount,
+            });
+        }
+
+        #[ink(message)]
+        pub fn unstake(&mut self, amount: u128) {
+            assert!(!self.paused, "Contract is paused");
+            let sender = self.env().caller();
+            let staked = self.staked_of(sender);
+            assert!(staked >= amount, "Not enough staked");
+
+            self.settle_rewards(sender);
+            self.staked.insert(sender, staked - amount);
+            let balance = self.balance_of(sender).checked_add(amount).expect("Arithmetic overflow in unstake");
+            self.set_balance(sender, balance);
+
+            self.env().emit_event(Unstaked {
+                account: sender,
+                amount,
+            });
+        }
+
+        /// Mints the caller's accrued staking rewards to them.
+        #[ink(message)]
+        pub fn claim_rewards(&mut self) {
+            assert!(!self.paused, "Contract is paused");
+            let sender = self.env().caller();
+            self.settle_rewards(sender);
+            let amount = self.unclaimed_rewards.take(&sender).unwrap_or(0);
+            if amount == 0 {
+                return;
+            }
+
+            let new_supply = self.total_supply.checked_add(amount).expect("Arithmetic overflow in claim");
+            assert!(self.max_supply == 0 || new_supply <= self.max_supply, "Max supply exceeded");
+            let balance = self.balance_of(sender).checked_add(amount).expect("Arithmetic overflow in claim");
+            self.set_balance(sender, balance);
+            self.set_total_supply(new_supply);
+
+            self.env().emit_event(RewardsClaimed {
+                account: sender,
+                amount,
+            });
+            self.env().emit_event(Transfer {
+                from: Self::zero_address(),
+                to: sender,
+                amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Mints `amount` tokens to the caller against the primary collateral and
+        /// returns the collateral charged.
+        #[ink(message)]
+        pub fn mint(&mut self, amount: u128) -> u128 {
+            let sender = self.env().caller();
+            self.mint_to(sender, amount)
+        }
+
+        #[ink(message)]
+        pub fn mint_to(&mut self, to: AccountId, amount: u128) -> u128 {
+            self.only_role(MINTER_ROLE);
+            self.mint_against(self.collateral_address, to, amount, u128::MAX)
+        }
+
+        /// Same as `mint`, but traps if more than `max_collateral` would be taken.
+        #[ink(message)]
+        pub fn mint_with_max_collateral(&mut self, amount: u128, max_collateral: u128) -> u128 {
+            self.only_role(MINTER_ROLE);
+            let sender = self.env().caller();
+            self.mint_against(self.collateral_address, sender, amount, max_collateral)
+        }
+
+        /// Same as `mint`, but deposits `collateral_token`, which must be the
+        /// primary collateral or a type registered with `add_collateral_type`.
+        #[ink(message)]
+        pub fn mint_with(&mut self, collateral_token: AccountId, amount: u128) -> u128 {
+            self.only_role(MINTER_ROLE);
+            let sender = self.env().caller();
+            self.mint_against(collateral_token, sender, amount, u128::MAX)
+        }
+
+        /// Same as `mint_to`, but open to anyone the owner granted a mint allowance,
+        /// which `amount` is taken out of.
+        #[ink(message)]
+        pub fn mint_as(&mut self, to: AccountId, amount: u128) -> u128 {
+            let minter = self.env().caller();
+            let allowance = self.mint_allowance_of(minter);
+            assert!(allowance >= amount, "Mint allowance exceeded");
+            self.mint_allowance.insert(minter, allowance - amount);
+            self.mint_against(self.collateral_address, to, amount, u128::MAX)
+        }
+
+        /// Mints `amounts[i]` to `recipients[i]` against the primary collateral,
+        /// pulling the collateral for the whole batch in a single transfer.
+        #[ink(message)]
+        pub fn batch_mint(&mut self, recipients: Vec<AccountId>, amounts: Vec<u128>) -> u128 {
+            self.only_role(MINTER_ROLE);
+            assert!(recipients.len() == amounts.len(), "Recipients and amounts length mismatch");
+            for (to, amount) in recipients.iter().zip(amounts.iter()) {
+                assert!(!Self::is_zero_address(to), "Zero address not allowed");
+                assert!(*amount >= self.min_mint_amount, "Below minimum mint amount");
+            }
+            self.lock();
+            assert!(!self.paused, "Contract is paused");
+            let total = amounts
+                .iter()
+                .try_fold(0u128, |total, amount| total.checked_add(*amount))
+                .expect("Arithmetic overflow in mint");
+            let new_supply = self.total_supply.checked_add(total).expect("Arithmetic overflow in mint");
+            assert!(self.max_supply == 0 || new_supply <= self.max_supply, "Max supply exceeded");
+            let minted_in_block = self.minted_in_current_block().checked_add(total).expect("Arithmetic overflow in mint");
+            assert!(
+                self.max_mint_per_block == 0 || minted_in_block <= self.max_mint_per_block,
+                "Mint rate limit exceeded"
+            );
+            self.mint_block = self.env().block_number();
+            self.minted_in_block = minted_in_block;
+            let now = self.env().block_timestamp();
+            for to in recipients.iter() {
+                assert!(now >= self.next_mint_time(*to), "Mint cooldown active");
+                if self.mint_cooldown > 0 {
+                    self.last_mint_at.insert(*to, now);
+                }
+            }
+            let sender = self.env().caller();
+            let price = self.refresh_price(self.collateral_address);
+            let collateral_amount = self.collateral_for(total, price, self.mint_rounding).expect("Arithmetic overflow in mint");
+            self.assert_within_collateral_cap(collateral_amount);
+            assert!(
+                CollateralRef::from_account_id(self.collateral_address).transfer_from(sender, self.env().account_id(), collateral_amount),
+                "Collateral transfer failed"
+            );
+
+            self.collateral_pool = self.collateral_pool.checked_add(collateral_amount).expect("Arithmetic overflow in mint");
+            assert!(self.collateral_pool > 0, "Collateral pool should be greater than 0");
+            // The caller paid for the whole batch
+            let contributed = self.collateral_contributed_by(sender).saturating_add(collateral_amount);
+            self.collateral_contributed.insert(sender, contributed);
+            for (to, amount) in recipients.iter().zip(amounts.iter()) {
+                let balance = self.balance_of(*to).checked_add(*amount).expect("Arithmetic overflow in mint");
+                self.set_balance(*to, balance);
+            }
+            self.set_total_supply(new_supply);
+            self.total_minted = self.total_minted.saturating_add(total);
+            assert!(
+                self.collateralization_ratio() >= self.min_collateral_ratio,
+                "Would breach min collateral ratio"
+            );
+
+            self.unlock();
+
+            for (to, amount) in recipients.into_iter().zip(amounts) {
+                // Each recipient's share, as if minted on its own
+                let collateral = self.collateral_for(amount, price, self.mint_rounding).unwrap_or(collateral_amount);
+                self.env().emit_event(Minted {
+                    from: sender,
+                    to,
+                    amount,
+                    collateral,
+                });
+                self.env().emit_event(Transfer {
+                    from: Self::zero_address(),
+                    to,
+                    amount,
+                    memo: self.transfer_memo(),
+                });
+            }
+            collateral_amount
+        }
+
+        fn mint_against(&mut self, token: AccountId, to: AccountId, amount: u128, max_collateral: u128) -> u128 {
+            self.lock();
+            let new_supply = self.validate_mint(to, amount);
+            let sender = self.env().caller();
+            let price = self.refresh_price(token);
+            let collateral_amount = self.collateral_for(amount, price, self.mint_rounding).expect("Arithmetic overflow in mint");
+            assert!(collateral_amount <= max_collateral, "Slippage exceeded");
+            if token == self.collateral_address {
+                self.assert_within_collateral_cap(collateral_amount);
+            }
+            assert!(
+                CollateralRef::from_account_id(token).transfer_from(sender, self.env().account_id(), collateral_amount),
+                "Collateral transfer failed"
+            );
+
+            let pool = self.collateral_pool_of(token).checked_add(collateral_amount).expect("Arithmetic overflow in mint");
+            assert!(pool > 0, "Collateral pool should be greater than 0");
+            self.set_collateral_pool(token, pool);
+            if token == self.collateral_address {
+                let contributed = self.collateral_contributed_by(to).saturating_add(collateral_amount);
+                self.collateral_contributed.insert(to, contributed);
+            }
+
+            let balance = self.balance_of(to).checked_add(amount).expect("Arithmetic overflow in mint");
+            self.set_balance(to, balance);
+            self.set_total_supply(new_supply);
+            self.total_minted = self.total_minted.saturating_add(amount);
+            assert!(
+                self.collateralization_ratio() >= self.min_collateral_ratio,
+                "Would breach min collateral ratio"
+            );
+
+            self.unlock();
+
+            self.env().emit_event(Minted {
+                from: sender,
+                to,
+                amount,
+                collateral: collateral_amount,
+            });
+            self.env().emit_event(Transfer {
+                from: Self::zero_address(),
+                to,
+                amount,
+                memo: self.transfer_memo(),
+            });
+            collateral_amount
+        }
+
+        /// Checks every mint of `amount` tokens to `to` has to pass, counting it
+        /// towards the per-block limit and `to`'s cooldown. Returns the total supply
+        /// after the mint.
+        fn validate_mint(&mut self, to: AccountId, amount: u128) -> u128 {
+            assert!(!Self::is_zero_address(&to), "Zero address not allowed");
+            assert!(amount >= self.min_mint_amount, "Below minimum mint amount");
+            assert!(!self.paused, "Contract is paused");
+            let new_supply = self.total_supply.checked_add(amount).expect("Arithmetic overflow in mint");
+            assert!(self.max_supply == 0 || new_supply <= self.max_supply, "Max supply exceeded");
+            let minted_in_block = self.minted_in_current_block().checked_add(amount).expect("Arithmetic overflow in mint");
+            assert!(
+                self.max_mint_per_block == 0 || minted_in_block <= self.max_mint_per_block,
+                "Mint rate limit exceeded"
+            );
+            self.mint_block = self.env().block_number();
+            self.minted_in_block = minted_in_block;
+            let now = self.env().block_timestamp();
+            assert!(now >= self.next_mint_time(to), "Mint cooldown active");
+            if self.mint_cooldown > 0 {
+                self.last_mint_at.insert(to, now);
+            }
+            new_supply
+        }
+
+        #[ink(message)]
+        pub fn redeem(&mut self, amount: u128) {
+            let sender = self.env().caller();
+            self.redeem_against(self.collateral_address, amount, 0, sender)
+        }
+
+        /// Same as `redeem`, but sends the collateral to `collateral_recipient`.
+        #[ink(message)]
+        pub fn redeem_to(&mut self, amount: u128, collateral_recipient: AccountId) {
+            self.redeem_against(self.collateral_address, amount, 0, collateral_recipient)
+        }
+
+        /// Same as `redeem`, but traps if less than `min_collateral` would be paid out.
+        #[ink(message)]
+        pub fn redeem_with_min_collateral(&mut self, amount: u128, min_collateral: u128) {
+            let sender = self.env().caller();
+            self.redeem_against(self.collateral_address, amount, min_collateral, sender)
+        }
+
+        /// Same as `redeem`, but pays out of the `collateral_token` pool.
+        #[ink(message)]
+        pub fn redeem_with(&mut self, collateral_token: AccountId, amount: u128) {
+            let sender = self.env().caller();
+            self.redeem_against(collateral_token, amount, 0, sender)
+        }
+
+        /// Redeems the caller's whole balance for primary collateral. Whatever the
+        /// pool can't cover right now without dropping below `collateral_floor`
+        /// goes into the redemption queue. Returns the
+        /// collateral `(paid, escrowed, queued)`: paid out right away, escrowed for
+        /// `redeem_delay` and waiting in the queue.
+        #[ink(message)]
+        pub fn redeem_all(&mut self) -> (u128, u128, u128) {
+            let sender = self.env().caller();
+            let balance = self.balance_of(sender);
+            assert!(balance > 0, "Nothing to redeem");
+            let price = self.fetch_price();
+            let available = self
+                .collateral_pool
+                .saturating_sub(self.redemption_queued)
+                .saturating_sub(self.collateral_floor);
+
+            // Rounding up covers whatever the collateral ledger pays back
+            let needed = self.collateral_for(balance, price, RoundingMode::Up).unwrap_or(u128::MAX);
+            let immediate = if needed <= available {
+                balance
+            } else {
+                // Inverts the collateral formula, so the rounded up collateral for
+                // `immediate` still fits into `available`
+                mul_div_round(
+                    U256::from(available) * U256::from(BASIS_POINTS),
+                    U256::from(self.price_scale()),
+                    U256::from(price) * U256::from(self.collateral_ratio),
+                    RoundingMode::Down,
+                )
+                .unwrap_or(0)
+                .min(balance)
+            };
+
+            let pool_before = self.collateral_pool;
+            if immediate > 0 {
+                self.redeem_against(self.collateral_address, immediate, 0, sender);
+            }
+            let redeemed = pool_before - self.collateral_pool;
+            let pending_before = self.pending_redemption_of(sender);
+            if immediate < balance {
+                self.request_redeem(balance - immediate);
+            }
+            let queued = self.pending_redemption_of(sender) - pending_before;
+            if self.redeem_delay > 0 {
+                (0, redeemed, queued)
+            } else {
+                (redeemed, 0, queued)
+            }
+        }
+
+        fn redeem_against(&mut self, token: AccountId, amount: u128, min_collateral: u128, recipient: AccountId) {
+            self.lock();
+            assert!(!self.paused, "Contract is paused");
+            let sender = self.env().caller();
+            assert!(!self.is_frozen(sender), "Account is frozen");
+            assert!(!Self::is_zero_address(&recipient), "Zero address not allowed");
+
+            let balance = self.balance_of(sender);
+            assert!(balance >= amount, "Not enough balance to redeem");
+            self.assert_collateral_backed(amount);
+            self.count_redeemed(amount);
+
+            let collateral_amount = self.redeemable_collateral(token, sender, amount, balance);
+
+            let pool = self.collateral_pool_of(token);
+            // Queued claims come first
+            let available = if token == self.collateral_address {
+                pool.saturating_sub(self.redemption_queued)
+            } else {
+                pool
+            };
+            assert!(available >= collateral_amount, "Not enough collateral in the pool");
+            let fee = mul_div(collateral_amount, self.redeem_fee_bps, BASIS_POINTS).expect("Arithmetic overflow in redeem");
+            let payout = collateral_amount - fee;
+            assert!(payout >= min_collateral, "Slippage exceeded");
+            if token == self.collateral_address {
+                self.assert_above_floor(payout);
+            }
+
+            self.set_balance(sender, balance - amount);
+            self.set_total_supply(self.total_supply.checked_sub(amount).expect("Arithmetic overflow in redeem"));
+            self.total_redeemed = self.total_redeemed.saturating_add(amount);
+            self.set_collateral_pool(token, pool - payout);
+            if token == self.collateral_address && self.redeem_delay > 0 {
+                self.escrow_collateral(recipient, payout);
+            } else {
+                assert!(
+                    CollateralRef::from_account_id(token).transfer(recipient, payout),
+                    "Collateral transfer failed"
+                );
+            }
+
+            self.unlock();
+
+            if fee > 0 {
+                self.record_fee(self.env().account_id(), fee);
+                self.env().emit_event(RedeemFeeCollected {
+                    from: sender,
+                    amount: fee,
+                });
+            }
+
+            self.env().emit_event(Redeemed {
+                from: sender,
+                to: recipient,
+                amount,
+                collateral: payout,
+            });
+            self.env().emit_event(Transfer {
+                from: sender,
+                to: Self::zero_address(),
+                amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Adds `amount` to the tokens redeemed in the current block, trapping past
+        /// `max_redeem_per_block`.
+        fn count_redeemed(&mut self, amount: u128) {
+            let redeemed_in_block = self.redeemed_in_current_block().checked_add(amount).expect("Arithmetic overflow in redeem");
+            assert!(
+                self.max_redeem_per_block == 0 || redeemed_in_block <= self.max_redeem_per_block,
+                "Redeem rate limit exceeded"
+            );
+            self.redeem_block = self.env().block_number();
+            self.redeemed_in_block = redeemed_in_block;
+        }
+
+        /// Collateral `sender` gets for burning `amount` of its `balance`, drawing
+        /// down its collateral ledger.
+        fn redeemable_collateral(&mut self, token: AccountId, sender: AccountId, amount: u128, balance: u128) -> u128 {
+            let price = self.refresh_price(token);
+            let mut collateral_amount = self.collateral_for(amount, price, self.redeem_rounding).expect("Arithmetic overflow in redeem");
+            let contributed = self.collateral_contributed_by(sender);
+            if token == self.collateral_address && contributed > 0 && balance > 0 {
+                // Pay back the account's own deposits pro rata, so rounding leaves no
+                // dust behind, but never more than the live price entitles it to
+                let share = mul_div(contributed, amount, balance).unwrap_or(contributed);
+                let entitled = self.collateral_for(amount, price, RoundingMode::Up).expect("Arithmetic overflow in redeem");
+                collateral_amount = share.min(entitled);
+                self.collateral_contributed.insert(sender, contributed - share);
+            }
+            collateral_amount
+        }
+
+        /// Adds `amount` of primary collateral to `account`'s escrow and restarts
+        /// its `redeem_delay`.
+        fn escrow_collateral(&mut self, account: AccountId, amount: u128) {
+            let (pending, _) = self.pending_collateral_of(account);
+            let pending = pending.checked_add(amount).expect("Arithmetic overflow in redeem");
+            let release_at = self.env().block_timestamp().saturating_add(self.redeem_delay);
+            self.pending_collateral.insert(account, (pending, release_at));
+            self.total_escrowed = self.total_escrowed.checked_add(amount).expect("Arithmetic overflow in redeem");
+        }
+
+        /// Pays out the caller's escrowed collateral once its release time passed.
+        #[ink(message)]
+        pub fn claim_redeemed_collateral(&mut self) {
+            self.lock();
+            let account = self.env().caller();
+            let (amount, release_at) = self.pending_collateral_of(account);
+            assert!(amount > 0, "No collateral to claim");
+            assert!(self.env().block_timestamp() >= release_at, "Collateral still locked");
+
+            self.pending_collateral.take(&account);
+            self.total_escrowed -= amount;
+            assert!(
+                CollateralRef::from_account_id(self.collateral_address).transfer(account, amount),
+                "Collateral transfer failed"
+            );
+
+            self.unlock();
+
+            self.env().emit_event(RedeemedCollateralClaimed { account, amount });
+        }
+
+        /// Burns `amount` of the caller's tokens and queues the collateral they
+        /// redeem for, to be paid out by `process_redemptions` once the pool can
+        /// cover it.
+        #[ink(message)]
+        pub fn request_redeem(&mut self, amount: u128) {
+            self.lock();
+            assert!(!self.paused, "Contract is paused");
+            let sender = self.env().caller();
+            assert!(!self.is_frozen(sender), "Account is frozen");
+
+            let balance = self.balance_of(sender);
+            assert!(balance >= amount, "Not enough balance to redeem");
+            self.assert_collateral_backed(amount);
+            self.count_redeemed(amount);
+            let collateral_amount = self.redeemable_collateral(self.collateral_address, sender, amount, balance);
+            let fee = mul_div(collateral_amount, self.redeem_fee_bps, BASIS_POINTS).expect("Arithmetic overflow in redeem");
+            let claim = collateral_amount - fee;
+
+            self.set_balance(sender, balance - amount);
+            self.set_total_supply(self.total_supply.checked_sub(amount).expect("Arithmetic overflow in redeem"));
+            self.total_redeemed = self.total_redeemed.saturating_add(amount);
+            self.redemption_queue.push((sender, claim));
+            let pending = self.pending_redemption_of(sender).checked_add(claim).expect("Arithmetic overflow in redeem");
+            self.pending_redemptions.insert(sender, pending);
+            self.redemption_queued = self.redemption_queued.checked_add(claim).expect("Arithmetic overflow in redeem");
+
+            self.unlock();
+
+            if fee > 0 {
+                self.record_fee(self.env().account_id(), fee);
+                self.env().emit_event(RedeemFeeCollected {
+                    from: sender,
+                    amount: fee,
+                });
+            }
+
+            self.env().emit_event(RedeemRequested {
+                account: sender,
+                amount,
+                collateral: claim,
+            });
+            self.env().emit_event(Transfer {
+                from: sender,
+                to: Self::zero_address(),
+                amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Pays out queued redemptions in order for as long as the pool covers the
+        /// next one without dropping below `collateral_floor`. Like `redeem`, the
+        /// collateral is escrowed for `redeem_delay`.
+        #[ink(message)]
+        pub fn process_redemptions(&mut self) {
+            self.lock();
+            let mut head = self.redemption_queue_head;
+            while let Some(&(account, claim)) = self.redemption_queue.get(head) {
+                if self.collateral_pool < claim || self.collateral_pool - claim < self.collateral_floor {
+                    break;
+                }
+                self.collateral_pool -= claim;
+                self.redemption_queued -= claim;
+                let pending = self.pending_redemption_of(account);
+                self.pending_redemptions.insert(account, pending - claim);
+                if self.redeem_delay > 0 {
+                    self.escrow_collateral(account, claim);
+                } else {
+                    assert!(
+                        CollateralRef::from_account_id(self.collateral_address).transfer(account, claim),
+                        "Collateral transfer failed"
+                    );
+                }
+                self.env().emit_event(RedemptionProcessed {
+                    account,
+                    collateral: claim,
+                });
+                head += 1;
+            }
+            self.redemption_queue_head = head;
+
+            self.unlock();
+        }
+
+        /// Locks `collateral` in the caller's debt position and mints `mint_amount`
+        /// tokens against it, as long as the position stays above both
+        /// `position_min_ratio` and `min_collateral_ratio`. The mint is subject to
+        /// the same limits as `mint`.
+        #[ink(message)]
+        pub fn open_position(&mut self, collateral: u128, mint_amount: u128) {
+            self.lock();
+            let sender = self.env().caller();
+            assert!(!self.is_frozen(sender), "Account is frozen");
+            let new_supply = self.validate_mint(sender, mint_amount);
+
+            let position_collateral = self.position_collateral_of(sender).checked_add(collateral).expect("Arithmetic overflow in mint");
+            let debt = self.debt_of(sender).checked_add(mint_amount).expect("Arithmetic overflow in mint");
+            let price = self.refresh_price(self.collateral_address);
+            let health = self.health_of(position_collateral, debt, price);
+            assert!(health >= self.position_min_ratio, "Position undercollateralized");
+            assert!(health >= self.min_collateral_ratio, "Would breach min collateral ratio");
+            assert!(
+                CollateralRef::from_account_id(self.collateral_address).transfer_from(sender, self.env().account_id(), collateral),
+                "Collateral transfer failed"
+            );
+
+            self.position_collateral.insert(sender, position_collateral);
+            self.debt.insert(sender, debt);
+            self.total_debt = self.total_debt.checked_add(mint_amount).expect("Arithmetic overflow in mint");
+            self.total_position_collateral =
+                self.total_position_collateral.checked_add(collateral).expect("Arithmetic overflow in mint");
+            let balance = self.balance_of(sender).checked_add(mint_amount).expect("Arithmetic overflow in mint");
+            self.set_balance(sender, balance);
+            self.set_total_supply(new_supply);
+
+            self.unlock();
+
+            self.env().emit_event(PositionUpdated {
+                account: sender,
+                collateral: position_collateral,
+                debt,
+            });
+            self.env().emit_event(Transfer {
+                from: Self::zero_address(),
+                to: sender,
+                amount: mint_amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Burns `amount` of the caller's tokens against their debt. Repaying the
+        /// debt in full releases the position's collateral.
+        #[ink(message)]
+        pub fn repay(&mut self, amount: u128) {
+            self.lock();
+            let sender = self.env().caller();
+            let debt = self.debt_of(sender);
+            assert!(amount <= debt, "Repay exceeds debt");
+            let balance = self.balance_of(sender);
+            assert!(balance >= amount, "Not enough balance to repay");
+
+            self.set_balance(sender, balance - amount);
+            self.set_total_supply(self.total_supply - amount);
+            let debt = debt - amount;
+            self.debt.insert(sender, debt);
+            self.total_debt -= amount;
+            let mut collateral = self.position_collateral_of(sender);
+            if debt == 0 && collateral > 0 {
+                self.position_collateral.take(&sender);
+                self.total_position_collateral -= collateral;
+                assert!(
+                    CollateralRef::from_account_id(self.collateral_address).transfer(sender, collateral),
+                    "Collateral transfer failed"
+                );
+                collateral = 0;
+            }
+
+            self.unlock();
+
+            self.env().emit_event(PositionUpdated {
+                account: sender,
+                collateral,
+                debt,
+            });
+            self.env().emit_event(Transfer {
+                from: sender,
+                to: Self::zero_address(),
+                amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Mints the transferred native value to the caller as tokens, 1:1.
+        #[ink(message, payable)]
+        pub fn deposit(&mut self) {
+            assert!(!self.paused, "Contract is paused");
+            let sender = self.env().caller();
+            assert!(!self.is_frozen(sender), "Account is frozen");
+            let amount = self.env().transferred_value();
+            let new_supply = self.total_supply.checked_add(amount).expect("Arithmetic overflow in deposit");
+            assert!(self.max_supply == 0 || new_supply <= self.max_supply, "Max supply exceeded");
+
+            self.native_reserve = self.native_reserve.checked_add(amount).expect("Arithmetic overflow in deposit");
+            let balance = self.balance_of(sender).checked_add(amount).expect("Arithmetic overflow in deposit");
+            self.set_balance(sender, balance);
+            self.set_total_supply(new_supply);
+
+            self.env().emit_event(Deposited {
+                account: sender,
+                amount,
+            });
+            self.env().emit_event(Transfer {
+                from: Self::zero_address(),
+                to: sender,
+                amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Burns `amount` of the caller's tokens and sends the same native value back.
+        /// Only the part of the supply the native reserve backs can be withdrawn,
+        /// just as redemptions only reach the collateral-backed part.
+        #[ink(message)]
+        pub fn withdraw(&mut self, amount: u128) {
+            self.lock();
+            assert!(!self.paused, "Contract is paused");
+            let sender = self.env().caller();
+            assert!(!self.is_frozen(sender), "Account is frozen");
+            let balance = self.balance_of(sender);
+            assert!(balance >= amount, "Not enough balance to withdraw");
+            assert!(self.native_reserve >= amount, "Not enough native reserve");
+
+            self.native_reserve -= amount;
+            self.set_balance(sender, balance - amount);
+            self.set_total_supply(self.total_supply - amount);
+            self.env().transfer(sender, amount).expect("Native transfer failed");
+
+            self.unlock();
+
+            self.env().emit_event(Withdrawn {
+                account: sender,
+                amount,
+            });
+            self.env().emit_event(Transfer {
+                from: sender,
+                to: Self::zero_address(),
+                amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Mints `amount` uncollateralized tokens to `target`, calls its
+        /// `on_flash_mint` hook with `data` and then burns `amount` back from it,
+        /// moving the flash fee on top to the fee collector.
+        ///
+        /// Traps unless `target` holds `amount` plus the fee once the hook returns,
+        /// so the total supply is back where it started when the call ends.
+        #[ink(message)]
+        pub fn flash_mint(&mut self, amount: u128, target: AccountId, data: Vec<u8>) {
+            assert!(!self.paused, "Contract is paused");
+            self.lock();
+            let initiator = self.env().caller();
+            let fee = mul_div(amount, self.flash_fee_bps, BASIS_POINTS).expect("Arithmetic overflow in flash mint");
+            let repayment = amount.checked_add(fee).expect("Arithmetic overflow in flash mint");
+            let total_supply = self.total_supply;
+
+            let balance = self.balance_of(target).checked_add(amount).expect("Arithmetic overflow in flash mint");
+            self.set_balance(target, balance);
+            self.set_total_supply(total_supply.checked_add(amount).expect("Arithmetic overflow in flash mint"));
+            self.env().emit_event(FlashMint { target, amount });
+            self.env().emit_event(Transfer {
+                from: Self::zero_address(),
+                to: target,
+                amount,
+                memo: self.transfer_memo(),
+            });
+
+            assert!(
+                FlashBorrowerRef::from_account_id(target).on_flash_mint(initiator, amount, fee, data),
+                "Flash mint callback failed"
+            );
+
+            let balance = self.balance_of(target);
+            assert!(balance >= repayment, "Flash mint not repaid");
+            self.set_balance(target, balance - repayment);
+            self.set_total_supply(total_supply);
+            if fee > 0 {
+                let collector = self.fee_collector;
+                let collector_balance = self.balance_of(collector).checked_add(fee).expect("Arithmetic overflow in flash mint");
+                self.set_balance(collector, collector_balance);
+                self.record_fee(collector, fee);
+            }
+
+            self.unlock();
+
+            self.env().emit_event(FlashMintRepaid { target, amount, fee });
+            self.env().emit_event(Transfer {
+                from: target,
+                to: Self::zero_address(),
+                amount,
+                memo: self.transfer_memo(),
+            });
+            if fee > 0 {
+                self.env().emit_event(Transfer {
+                    from: target,
+                    to: self.fee_collector,
+                    amount: fee,
+                    memo: self.transfer_memo(),
+                });
+            }
+        }
+
+        #[ink(message)]
+        pub fn burn(&mut self, amount: u128) {
+            let sender = self.env().caller();
+            self.burn_tokens(sender, amount)
+        }
+
+        #[ink(message)]
+        pub fn burn_from(&mut self, from: AccountId, amount: u128) {
+            let sender = self.env().caller();
+            self.spend_allowance(from, sender, amount).expect("Not enough allowance");
+            self.burn_tokens(from, amount)
+        }
+
+        fn burn_tokens(&mut self, from: AccountId, amount: u128) {
+            let balance = self.balance_of(from);
+            assert!(balance >= amount, "Not enough balance to burn");
+
+            self.set_balance(from, balance - amount);
+            self.set_total_supply(self.total_supply - amount);
+
+            self.env().emit_event(Burned {
+                from,
+                amount,
+            });
+            self.env().emit_event(Transfer {
+                from,
+                to: Self::zero_address(),
+                amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Burns `amount` of `account`'s tokens while the pool is undercollateralized and
+        /// pays the caller the backing collateral plus the liquidation bonus.
+        ///
+        /// Unless the caller liquidates their own tokens, this spends the caller's
+        /// allowance over `account`.
+        #[ink(message)]
+        pub fn liquidate(&mut self, account: AccountId, amount: u128) {
+            self.lock();
+            let liquidator = self.env().caller();
+            assert!(self.collateralization_ratio() < self.liquidation_ratio, "Collateral ratio is healthy");
+
+            if liquidator != account {
+                self.spend_allowance(account, liquidator, amount).expect("Not enough allowance");
+            }
+
+            let balance = self.balance_of(account);
+            assert!(balance >= amount, "Not enough balance to liquidate");
+            self.assert_collateral_backed(amount);
+
+            self.collateral_price = self.fetch_price();
+            let collateral_amount = self
+                .collateral_for(amount, self.collateral_price, RoundingMode::Down)
+                .and_then(|collateral| mul_div(collateral, BASIS_POINTS + self.liquidation_bonus, BASIS_POINTS))
+                .expect("Arithmetic overflow in liquidate");
+            assert!(self.collateral_pool >= collateral_amount, "Not enough collateral in the pool");
+            self.assert_above_floor(collateral_amount);
+
+            self.set_balance(account, balance - amount);
+            self.set_total_supply(self.total_supply - amount);
+            self.collateral_pool -= collateral_amount;
+            assert!(
+                CollateralRef::from_account_id(self.collateral_address).transfer(liquidator, collateral_amount),
+                "Collateral transfer failed"
+            );
+
+            self.unlock();
+
+            self.env().emit_event(Liquidation {
+                liquidator,
+                account,
+                amount,
+                collateral: collateral_amount,
+            });
+            self.env().emit_event(Transfer {
+                from: account,
+                to: Self::zero_address(),
+                amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Adds `amount` of the caller's collateral to the pool without minting
+        /// anything in return, e.g. to restore health after a price drop.
+        #[ink(message)]
+        pub fn donate_collateral(&mut self, amount: u128) {
+            self.lock();
+            let donor = self.env().caller();
+            self.assert_within_collateral_cap(amount);
+            assert!(
+                CollateralRef::from_account_id(self.collateral_address).transfer_from(donor, self.env().account_id(), amount),
+                "Collateral transfer failed"
+            );
+            self.collateral_pool = self.collateral_pool.checked_add(amount).expect("Arithmetic overflow in donation");
+
+            self.unlock();
+
+            self.env().emit_event(CollateralDonated { donor, amount });
+        }
+
+        /// Books `amount` of collateral the pool earned externally. The collateral
+        /// has to be held by the contract already.
+        #[ink(message)]
+        pub fn accrue_yield(&mut self, amount: u128) {
+            assert!(Some(self.env().caller()) == self.yield_source, "Caller is not the yield source");
+            self.collateral_pool = self.collateral_pool.checked_add(amount).expect("Arithmetic overflow in yield");
+            self.total_yield_accrued = self.total_yield_accrued.saturating_add(amount);
+            self.env().emit_event(YieldAccrued { amount });
+        }
+
+        #[ink(message)]
+        pub fn withdraw_collateral(&mut self, amount: u128) {
+            self.only_owner();
+            self.assert_not_timelocked();
+            self.assert_not_multisig();
+            self.apply_withdraw_collateral(amount)
+        }
+
+        fn apply_withdraw_collateral(&mut self, amount: u128) {
+            self.lock();
+            assert!(amount <= self.excess_collateral(), "Not enough excess collateral");
+            self.assert_above_floor(amount);
+            let owner = self.owner;
+
+            self.collateral_pool -= amount;
+            assert!(
+                CollateralRef::from_account_id(self.collateral_address).transfer(owner, amount),
+                "Collateral transfer failed"
+            );
+
+            self.unlock();
+
+            self.env().emit_event(CollateralWithdrawn {
+                to: owner,
+                amount,
+            });
+        }
+
+        /// Replaces the contract code while keeping its storage; see the layout
+        /// requirements on [`Chest`].
+        #[ink(message)]
+        pub fn set_code_hash(&mut self, code_hash: [u8; 32]) {
+            self.only_owner();
+            self.assert_not_timelocked();
+            self.assert_not_multisig();
+            self.apply_code_hash(code_hash)
+        }
+
+        fn apply_code_hash(&mut self, code_hash: [u8; 32]) {
+            #[cfg(not(test))]
+            self.env().set_code_hash(&code_hash).expect("Failed to set code hash");
+            #[cfg(test)]
+            mock::set_code_hash(code_hash);
+
+            self.env().emit_event(CodeUpgraded {
+                code_hash: Hash::from(code_hash),
+            });
+        }
+
+        /// Sends `amount` of a foreign `token` held by this contract to `to`. The
+        /// collateral token is excluded since it backs the supply.
+        #[ink(message)]
+        pub fn rescue_tokens(&mut self, token: AccountId, to: AccountId, amount: u128) {
+            self.only_owner();
+            assert!(
+                token != self.collateral_address && !self.collaterals.contains_key(&token),
+                "Cannot rescue collateral"
+            );
+            // Foreign tokens speak the same transfer interface as the collateral token
+            assert!(
+                CollateralRef::from_account_id(token).transfer(to, amount),
+                "Token transfer failed"
+            );
+
+            self.env().emit_event(TokensRescued { token, to, amount });
+        }
+
+        /// Moves the whole primary collateral pool into `new_collateral` by swapping
+        /// it through `conversion_contract`, valuing the new collateral at
+        /// `new_price`. Traps if the swap would lower the value backing the supply,
+        /// or while position collateral, escrowed or queued redemptions or unclaimed
+        /// dividends are still owed in the old collateral.
+        #[ink(message)]
+        pub fn swap_collateral(&mut self, new_collateral: AccountId, new_price: u128, conversion_contract: AccountId) {
+            self.only_owner();
+            self.lock();
+            assert!(!self.collaterals.contains_key(&new_collateral), "Collateral type already registered");
+            assert!(
+                self.total_position_collateral == 0
+                    && self.total_escrowed == 0
+                    && self.redemption_queued == 0
+                    && self.dividend_reserve == 0,
+                "Collateral owed outside the pool"
+            );
+            self.assert_price_in_bounds(new_price);
+            let old_collateral = self.collateral_address;
+            let old_price = self.fetch_price();
+            let amount_in = self.collateral_pool;
+
+            assert!(
+                CollateralRef::from_account_id(old_collateral).transfer(conversion_contract, amount_in),
+                "Collateral transfer failed"
+            );
+            let amount_out = CollateralSwapRef::from_account_id(conversion_contract).swap(old_collateral, new_collateral, amount_in);
+            let old_value = U256::from(amount_in) * U256::from(old_price);
+            let new_value = U256::from(amount_out) * U256::from(new_price);
+            assert!(new_value >= old_value, "Swap would weaken backing");
+
+            self.collateral_address = new_collateral;
+            self.collateral_price = new_price;
+            self.price_updated_at = self.env().block_timestamp();
+            self.collateral_pool = amount_out;
+
+            self.unlock();
+
+            self.env().emit_event(CollateralSwapped {
+                old_collateral,
+                new_collateral,
+                amount_in,
+                amount_out,
+            });
+        }
+
+        /// Accepts `token` as additional collateral, valued at `price` whenever the
+        /// token itself reports none.
+        #[ink(message)]
+        pub fn add_collateral_type(&mut self, token: AccountId, price: u128) {
+            self.only_owner();
+            assert!(
+                token != self.collateral_address && !self.collaterals.contains_key(&token),
+                "Collateral type already registered"
+            );
+            self.collaterals.insert(token, CollateralInfo { pool: 0, price });
+
+            self.env().emit_event(CollateralTypeAdded { token, price });
+        }
+
+        /// Nominates `new_owner`, who becomes the owner once they call
+        /// `accept_ownership`.
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) {
+            self.only_owner();
+            self.pending_owner = Some(new_owner);
+        }
+
+        /// Completes the ownership transfer. The roles the previous owner held by
+        /// default move along, with the new owner getting `ADMIN_ROLE` to hand out
+        /// the others.
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) {
+            let new_owner = self.env().caller();
+            assert!(self.pending_owner == Some(new_owner), "Caller is not the pending owner");
+            let previous_owner = self.owner;
+            self.owner = new_owner;
+            self.pending_owner = None;
+
+            self.env().emit_event(OwnershipTransferred {
+                previous_owner,
+                new_owner,
+            });
+
+            for role in [ADMIN_ROLE, MINTER_ROLE, PAUSER_ROLE] {
+                if self.roles.take(&(role, previous_owner)).is_some() {
+                    self.env().emit_event(RoleRevoked {
+                        role,
+                        account: previous_owner,
+                        sender: new_owner,
+                    });
+                }
+            }
+            self.roles.insert((ADMIN_ROLE, new_owner), true);
+            self.env().emit_event(RoleGranted {
+                role: ADMIN_ROLE,
+                account: new_owner,
+                sender: new_owner,
+            });
+        }
+
+        #[ink(message)]
+        pub fn cancel_ownership_transfer(&mut self) {
+            self.only_owner();
+            self.pending_owner = None;
+        }
+
+        #[ink(message)]
+        pub fn grant_role(&mut self, role: RoleId, account: AccountId) {
+            self.only_role(ADMIN_ROLE);
+            self.roles.insert((role, account), true);
+            self.env().emit_event(RoleGranted {
+                role,
+                account,
+                sender: self.env().caller(),
+            });
+        }
+
+        #[ink(message)]
+        pub fn revoke_role(&mut self, role: RoleId, account: AccountId) {
+            self.only_role(ADMIN_ROLE);
+            self.roles.take(&(role, account));
+            self.env().emit_event(RoleRevoked {
+                role,
+                account,
+                sender: self.env().caller(),
+            });
+        }
+
+        /// Pauses the contract if the backing ratio at the live price has fallen
+        /// below `insolvency_threshold`. Callable by anyone, so the live price is
+        /// only read, never cached, and traps if it is outside the price bounds.
+        /// Traps while another message is running, e.g. from a flash mint
+        /// callback seeing the pool mid-update.
+        #[ink(message)]
+        pub fn check_health(&mut self) {
+            self.lock();
+            let backing_ratio = self.backing_ratio_at(self.fetch_price());
+            if !self.paused && self.insolvency_threshold != 0 && backing_ratio < self.insolvency_threshold {
+                self.paused = true;
+                self.env().emit_event(AutoPaused { backing_ratio });
+            }
+            self.unlock();
+        }
+
+        #[ink(message)]
+        pub fn pause(&mut self) {
+            self.only_role(PAUSER_ROLE);
+            self.paused = true;
+
+            self.env().emit_event(Paused {
+                account: self.env().caller(),
+            });
+        }
+
+        #[ink(message)]
+        pub fn unpause(&mut self) {
+            self.only_role(PAUSER_ROLE);
+            self.paused = false;
+
+            self.env().emit_event(Unpaused {
+                account: self.env().caller(),
+            });
+        }
+
+        #[ink(message)]
+        pub fn set_max_collateral_pool(&mut self, max_collateral_pool: u128) {
+            self.only_owner();
+            self.max_collateral_pool = max_collateral_pool;
+        }
+
+        #[ink(message)]
+        pub fn set_collateral_floor(&mut self, collateral_floor: u128) {
+            self.only_owner();
+            self.collateral_floor = collateral_floor;
+        }
+
+        #[ink(message)]
+        pub fn set_fee_exempt(&mut self, account: AccountId, exempt: bool) {
+            self.only_owner();
+            if exempt {
+                self.fee_exempt.insert(account, true);
+            } else {
+                self.fee_exempt.take(&account);
+            }
+        }
+
+        #[ink(message)]
+        pub fn set_transfer_fee(&mut self, fee_bps: u128, fee_collector: AccountId) {
+            self.only_owner();
+            assert!(fee_bps <= BASIS_POINTS, "Fee exceeds 100%");
+            self.fee_bps = fee_bps;
+            self.fee_collector = fee_collector;
+        }
+
+        #[ink(message)]
+        pub fn set_redeem_fee(&mut self, redeem_fee_bps: u128) {
+            self.only_owner();
+            assert!(redeem_fee_bps <= BASIS_POINTS, "Fee exceeds 100%");
+            self.redeem_fee_bps = redeem_fee_bps;
+        }
+
+        #[ink(message)]
+        pub fn set_liquidation_params(&mut self, liquidation_ratio: u128, liquidation_bonus: u128) {
+            self.only_owner();
+            self.liquidation_ratio = liquidation_ratio;
+            self.liquidation_bonus = liquidation_bonus;
+        }
+
+        /// Caps the amount of a single transfer, `0` lifting the cap. With
+        /// `exempt_owner` set the owner's own transfers are not capped.
+        #[ink(message)]
+        pub fn set_max_tx_amount(&mut self, max_tx_amount: u128, exempt_owner: bool) {
+            self.only_owner();
+            self.max_tx_amount = max_tx_amount;
+            self.owner_exempt_from_max_tx = exempt_owner;
+        }
+
+        /// Delays the release of redeemed primary collateral by `redeem_delay`
+        /// milliseconds, `0` paying it out with the redeem.
+        #[ink(message)]
+        pub fn set_redeem_delay(&mut self, redeem_delay: u64) {
+            self.only_owner();
+            self.redeem_delay = redeem_delay;
+        }
+
+        #[ink(message)]
+        pub fn set_insolvency_threshold(&mut self, insolvency_threshold: u128) {
+            self.only_owner();
+            self.insolvency_threshold = insolvency_threshold;
+        }
+
+        #[ink(message)]
+        pub fn set_min_collateral_ratio(&mut self, min_collateral_ratio: u128) {
+            self.only_owner();
+            self.min_collateral_ratio = min_collateral_ratio;
+        }
+
+        #[ink(message)]
+        pub fn set_rate_limits(&mut self, max_mint_per_block: u128, max_redeem_per_block: u128) {
+            self.only_owner();
+            self.max_mint_per_block = max_mint_per_block;
+            self.max_redeem_per_block = max_redeem_per_block;
+        }
+
+        #[ink(message)]
+        pub fn set_position_min_ratio(&mut self, position_min_ratio: u128) {
+            self.only_owner();
+            self.position_min_ratio = position_min_ratio;
+        }
+
+        /// Sets the contract every transfer has to be approved by, `None` to stop
+        /// checking transfers.
+        #[ink(message)]
+        pub fn set_compliance_contract(&mut self, compliance_contract: Option<AccountId>) {
+            self.only_owner();
+            self.compliance_contract = compliance_contract;
+        }
+
+        #[ink(message)]
+        pub fn set_allow_zero_transfers(&mut self, allow_zero_transfers: bool) {
+            self.only_owner();
+            self.allow_zero_transfers = allow_zero_transfers;
+        }
+
+        #[ink(message)]
+        pub fn set_reserve(&mut self, reserve_bps: u128) {
+            self.only_owner();
+            assert!(reserve_bps <= BASIS_POINTS, "Reserve exceeds 100%");
+            self.reserve_bps = reserve_bps;
+        }
+
+        #[ink(message)]
+        pub fn set_yield_source(&mut self, yield_source: Option<AccountId>) {
+            self.only_owner();
+            self.yield_source = yield_source;
+        }
+
+        #[ink(message)]
+        pub fn set_rounding_modes(&mut self, mint_rounding: RoundingMode, redeem_rounding: RoundingMode) {
+            self.only_owner();
+            self.mint_rounding = mint_rounding;
+            self.redeem_rounding = redeem_rounding;
+        }
+
+        /// Caps what each account can send within `window` milliseconds,
+        /// `max_transfer_per_window` of `0` lifting the cap.
+        #[ink(message)]
+        pub fn set_velocity_limit(&mut self, max_transfer_per_window: u128, window: u64) {
+            self.only_owner();
+            self.max_transfer_per_window = max_transfer_per_window;
+            self.velocity_window = window;
+        }
+
+        #[ink(message)]
+        pub fn set_dust_threshold(&mut self, dust_threshold: u128) {
+            self.only_owner();
+            self.dust_threshold = dust_threshold;
+        }
+
+        #[ink(message)]
+        pub fn set_strict_approve(&mut self, strict_approve: bool) {
+            self.only_owner();
+            self.strict_approve = strict_approve;
+        }
+
+        #[ink(message)]
+        pub fn set_min_mint_amount(&mut self, min_mint_amount: u128) {
+            self.only_owner();
+            self.min_mint_amount = min_mint_amount;
+        }
+
+        /// Lets `minter` mint up to `cap` tokens through `mint_as`, replacing any
+        /// allowance it had left.
+        #[ink(message)]
+        pub fn set_mint_allowance(&mut self, minter: AccountId, cap: u128) {
+            self.only_owner();
+            self.mint_allowance.insert(minter, cap);
+        }
+
+        #[ink(message)]
+        pub fn set_mint_cooldown(&mut self, mint_cooldown: u64) {
+            self.only_owner();
+            self.mint_cooldown = mint_cooldown;
+        }
+
+        #[ink(message)]
+        pub fn set_flash_fee(&mut self, flash_fee_bps: u128) {
+            self.only_owner();
+            assert!(flash_fee_bps <= BASIS_POINTS, "Fee exceeds 100%");
+            self.flash_fee_bps = flash_fee_bps;
+        }
+
+        /// Sets the staking reward per staked token per block, in basis points.
+        #[ink(message)]
+        pub fn set_reward_rate(&mut self, reward_rate: u128) {
+            self.only_owner();
+            self.reward_rate = reward_rate;
+        }
+
+        #[ink(message)]
+        pub fn set_name(&mut self, name: String) {
+            self.only_owner();
+            assert!(!name.is_empty() && name.len() <= MAX_METADATA_LENGTH, "Invalid name");
+            self.name = name;
+            self.emit_metadata_updated();
+        }
+
+        #[ink(message)]
+        pub fn set_symbol(&mut self, symbol: String) {
+            self.only_owner();
+            assert!(!symbol.is_empty() && symbol.len() <= MAX_METADATA_LENGTH, "Invalid symbol");
+            self.symbol = symbol;
+            self.emit_metadata_updated();
+        }
+
+        /// Sets the cached collateral price used while the collateral contract
+        /// reports none.
+        #[ink(message)]
+        pub fn update_price(&mut self, price: u128) {
+            self.only_owner();
+            self.assert_price_in_bounds(price);
+            let old_price = self.collateral_price;
+            self.collateral_price = price;
+            self.price_updated_at = self.env().block_timestamp();
+
+            self.env().emit_event(PriceUpdated {
+                old_price,
+                new_price: price,
+            });
+        }
+
+        /// Sets the collateral price to `price` as of `timestamp`, signed by the
+        /// oracle key.
+        ///
+        /// The signed message is the `Blake2x256` hash of the SCALE encoded tuple
+        /// `(contract, price, timestamp)`. Timestamps have to be newer than the
+        /// current price and not in the future.
+        #[ink(message)]
+        pub fn submit_signed_price(&mut self, price: u128, timestamp: u64, signature: [u8; 65]) {
+            let oracle = self.oracle.expect("Oracle not configured");
+            let now = self.env().block_timestamp();
+            assert!(timestamp <= now, "Price timestamp in the future");
+            assert!(
+                timestamp > self.price_updated_at && (self.max_price_age == 0 || now - timestamp <= self.max_price_age),
+                "Price data is stale"
+            );
+
+            let message = self.env().hash_encoded::<Blake2x256, _>(&(self.env().account_id(), price, timestamp));
+            let mut public_key = [0u8; 33];
+            self.env()
+                .ecdsa_recover(&signature, &message, &mut public_key)
+                .expect("Invalid signature");
+            let signer = AccountId::from(self.env().hash_bytes::<Blake2x256>(&public_key));
+            assert!(signer == oracle, "Invalid signature");
+            self.assert_price_in_bounds(price);
+
+            let old_price = self.collateral_price;
+            self.collateral_price = price;
+            self.price_updated_at = timestamp;
+
+            self.env().emit_event(PriceUpdated {
+                old_price,
+                new_price: price,
+            });
+        }
+
+        /// Takes the collateral price from prices signed by `oracle_pubkey` instead
+        /// of the collateral contract, `None` going back to the collateral contract.
+        #[ink(message)]
+        pub fn set_oracle_pubkey(&mut self, oracle_pubkey: Option<[u8; 33]>) {
+            self.only_owner();
+            self.oracle = oracle_pubkey.map(|public_key| AccountId::from(self.env().hash_bytes::<Blake2x256>(&public_key)));
+        }
+
+        #[ink(message)]
+        pub fn set_price_bounds(&mut self, min_price: u128, max_price: u128) {
+            self.only_owner();
+            assert!(min_price <= max_price, "Invalid price bounds");
+            self.min_price = min_price;
+            self.max_price = max_price;
+        }
+
+        #[ink(message)]
+        pub fn set_max_price_age(&mut self, max_price_age: u64) {
+            self.only_owner();
+            self.max_price_age = max_price_age;
+        }
+
+        #[ink(message)]
+        pub fn set_max_supply(&mut self, max_supply: u128) {
+            self.only_owner();
+            self.assert_not_multisig();
+            self.apply_max_supply(max_supply)
+        }
+
+        fn apply_max_supply(&mut self, max_supply: u128) {
+            assert!(max_supply == 0 || max_supply >= self.total_supply, "Max supply below total supply");
+            self.max_supply = max_supply;
+        }
+
+        /// Rescales every balance, stake and vesting schedule proportionally so the
+        /// total supply becomes `new_total_supply`. Snapshots keep the balances from
+        /// before the rebase.
+        #[ink(message)]
+        pub fn rebase(&mut self, new_total_supply: u128) {
+            self.only_owner();
+            let old_supply = self.total_supply;
+            assert!(old_supply > 0, "Nothing to rebase");
+            let old_factor = self.scaling_factor;
+            let scaling_factor = mul_div(old_factor, new_total_supply, old_supply).expect("Arithmetic overflow in rebase");
+            assert!(scaling_factor > 0, "Rebase too small");
+
+            // Balances change without going through `set_balance`, so book their
+            // dividends and checkpoint them here
+            let current = self.current_snapshot_id;
+            let holders: Vec<AccountId> = self.holders.iter().cloned().collect();
+            for account in holders {
+                self.settle_dividend(account);
+                if current > 0 {
+                    let balance = self.balance_of(account);
+                    let snapshots = self.account_snapshots.entry(account).or_insert_with(Vec::new);
+                    Self::update_snapshot(snapshots, current, balance);
+                }
+            }
+            // Staked and vested tokens count towards the supply as well
+            let stakers: Vec<AccountId> = self.staked.iter().map(|(account, _)| *account).collect();
+            for account in stakers {
+                self.settle_rewards(account);
+                let staked = mul_div(self.staked_of(account), scaling_factor, old_factor).expect("Arithmetic overflow in rebase");
+                self.staked.insert(account, staked);
+            }
+            let beneficiaries: Vec<AccountId> = self.vesting.iter().map(|(account, _)| *account).collect();
+            for beneficiary in beneficiaries {
+                if let Some(schedule) = self.vesting.get_mut(&beneficiary) {
+                    schedule.total = mul_div(schedule.total, scaling_factor, old_factor).expect("Arithmetic overflow in rebase");
+                    schedule.released = mul_div(schedule.released, scaling_factor, old_factor).expect("Arithmetic overflow in rebase");
+                }
+            }
+
+            self.scaling_factor = scaling_factor;
+            self.set_total_supply(new_total_supply);
+
+            self.env().emit_event(Rebase {
+                old_supply,
+                new_supply: new_total_supply,
+            });
+        }
+
+        #[ink(message)]
+        pub fn set_collateral_ratio(&mut self, collateral_ratio: u128) {
+            self.only_owner();
+            self.assert_not_timelocked();
+            self.apply_collateral_ratio(collateral_ratio)
+        }
+
+        fn apply_collateral_ratio(&mut self, collateral_ratio: u128) {
+            let old_ratio = self.collateral_ratio;
+            self.collateral_ratio = collateral_ratio;
+
+            self.env().emit_event(RatioUpdated {
+                old_ratio,
+                new_ratio: collateral_ratio,
+            });
+        }
+
+        #[ink(message)]
+        pub fn storage_version(&self) -> u16 {
+            self.storage_version
+        }
+
+        /// Brings storage left behind by older code one version forward. Call it
+        /// after `set_code_hash` until it reaches `STORAGE_VERSION`.
+        #[ink(message)]
+        pub fn migrate(&mut self) {
+            self.only_owner();
+            let from = self.storage_version;
+            assert!(from < STORAGE_VERSION, "Storage already migrated");
+            if from == 1 {
+                self.migrate_v1();
+            }
+            self.storage_version = from + 1;
+
+            self.env().emit_event(Migrated {
+                from,
+                to: self.storage_version,
+            });
+        }
+
+        /// Initializes the fields version 2 appended after `storage_version` to
+        /// the values `new` gives them.
+        fn migrate_v1(&mut self) {
+            self.dust_threshold = 0;
+            self.allow_zero_transfers = true;
+            self.max_transfer_per_window = 0;
+            self.velocity_window = 0;
+            self.transfer_windows = StorageHashMap::new();
+            self.redemption_queue = StorageVec::new();
+            self.pending_redemptions = StorageHashMap::new();
+            self.redemption_queued = 0;
+            self.mint_allowance = StorageHashMap::new();
+            self.spenders = StorageHashMap::new();
+            self.mint_rounding = RoundingMode::Up;
+            self.redeem_rounding = RoundingMode::Down;
+            self.yield_source = None;
+            self.total_yield_accrued = 0;
+            self.reserve_bps = 0;
+            self.whitelist_enabled = false;
+            self.transfer_whitelist = StorageHashMap::new();
+            self.non_circulating = Vec::new();
+            self.insolvency_threshold = 0;
+            self.max_tx_amount = 0;
+            self.owner_exempt_from_max_tx = false;
+            self.redeem_delay = 0;
+            self.pending_collateral = StorageHashMap::new();
+            self.fee_exempt = StorageHashMap::new();
+            self.collateral_floor = 0;
+            self.multisig_owners = Vec::new();
+            self.threshold = 0;
+            self.transactions = StorageHashMap::new();
+            self.confirmations = StorageHashMap::new();
+            self.next_transaction_id = 0;
+            self.oracle = None;
+            self.max_collateral_pool = 0;
+            self.flash_fee_bps = 0;
+            self.max_mint_per_block = 0;
+            self.max_redeem_per_block = 0;
+            self.mint_block = 0;
+            self.minted_in_block = 0;
+            self.redeem_block = 0;
+            self.redeemed_in_block = 0;
+            self.holders = StorageVec::new();
+            for holder in core::mem::take(&mut self.legacy_holders) {
+                self.holders.push(holder);
+            }
+            self.held_raw_supply = self
+                .holders
+                .iter()
+                .fold(0u128, |supply, holder| supply.saturating_add(*self.balances.get(holder).unwrap_or(&0)));
+            self.redemption_queue_head = 0;
+            self.total_debt = self.debt.values().fold(0u128, |total, debt| total.saturating_add(*debt));
+            self.total_position_collateral =
+                self.position_collateral.values().fold(0u128, |total, collateral| total.saturating_add(*collateral));
+            self.total_escrowed = self
+                .pending_collateral
+                .values()
+                .fold(0u128, |total, (pending, _)| total.saturating_add(*pending));
+            // Holders' owed dividends include the accrued ones, the rest only what was booked
+            let holders: Vec<AccountId> = self.holders.iter().cloned().collect();
+            self.dividend_reserve = holders
+                .iter()
+                .fold(0u128, |total, holder| total.saturating_add(self.claimable_dividend(*holder)));
+            for (account, owed) in self.dividends_owed.iter() {
+                if self.holder_index.get(account).is_none() {
+                    self.dividend_reserve = self.dividend_reserve.saturating_add(*owed);
+                }
+            }
+            // Nothing was swept before version 2
+            self.unswept_fees = StorageHashMap::new();
+            for (collector, collected) in self.fees_collected.iter() {
+                self.unswept_fees.insert(*collector, *collected);
+            }
+        }
+
+        #[ink(message)]
+        pub fn min_delay(&self) -> u64 {
+            self.min_delay
+        }
+
+        /// Identifier under which `action` is queued, the `Blake2x256` hash of its
+        /// SCALE encoding.
+        #[ink(message)]
+        pub fn action_id(&self, action: TimelockAction) -> [u8; 32] {
+            self.env().hash_encoded::<Blake2x256, _>(&action)
+        }
+
+        /// Earliest execution time of a queued action, `None` if it isn't queued.
+        #[ink(message)]
+        pub fn action_eta(&self, action_id: [u8; 32]) -> Option<u64> {
+            self.queued_actions.get(&action_id).cloned()
+        }
+
+        /// Changes the timelock delay directly while it is disabled; once enabled
+        /// the change itself has to be queued.
+        #[ink(message)]
+        pub fn set_min_delay(&mut self, min_delay: u64) {
+            self.only_owner();
+            self.assert_not_timelocked();
+            self.min_delay = min_delay;
+        }
+
+        /// Schedules the action with `action_id` to become executable at `eta`,
+        /// at least `min_delay` from now.
+        #[ink(message)]
+        pub fn queue_action(&mut self, action_id: [u8; 32], eta: u64) {
+            self.only_owner();
+            let earliest = self.env().block_timestamp().saturating_add(self.min_delay);
+            assert!(eta >= earliest, "Eta before minimum delay");
+            self.queued_actions.insert(action_id, eta);
+
+            self.env().emit_event(ActionQueued {
+                action_id: Hash::from(action_id),
+                eta,
+            });
+        }
+
+        /// Runs a queued `action` once its eta has passed. Collateral withdrawals
+        /// and code upgrades go through `execute_transaction` instead while the
+        /// multisig is set up.
+        #[ink(message)]
+        pub fn execute_action(&mut self, action: TimelockAction) {
+            self.only_owner();
+            if matches!(action, TimelockAction::WithdrawCollateral(_) | TimelockAction::SetCodeHash(_)) {
+                self.assert_not_multisig();
+            }
+            let action_id = self.take_ready_action(action.clone());
+
+            match action {
+                TimelockAction::SetCollateralRatio(collateral_ratio) => self.apply_collateral_ratio(collateral_ratio),
+                TimelockAction::WithdrawCollateral(amount) => self.apply_withdraw_collateral(amount),
+                TimelockAction::SetCodeHash(code_hash) => self.apply_code_hash(code_hash),
+                TimelockAction::SetMinDelay(min_delay) => self.min_delay = min_delay,
+            }
+
+            self.env().emit_event(ActionExecuted {
+                action_id: Hash::from(action_id),
+            });
+        }
+
+        #[ink(message)]
+        pub fn multisig_owners(&self) -> Vec<AccountId> {
+            self.multisig_owners.clone()
+        }
+
+        #[ink(message)]
+        pub fn threshold(&self) -> u8 {
+            self.threshold
+        }
+
+        /// Number of multisig owners who confirmed `transaction_id`.
+        #[ink(message)]
+        pub fn confirmation_count(&self, transaction_id: u32) -> u8 {
+            self.multisig_owners
+                .iter()
+                .filter(|owner| self.confirmations.contains_key(&(transaction_id, **owner)))
+                .count() as u8
+        }
+
+        /// Proposes `action` for the multisig owners to confirm, counting as the
+        /// caller's confirmation. Returns the transaction id.
+        #[ink(message)]
+        pub fn submit_transaction(&mut self, action: MultisigAction) -> u32 {
+            self.only_multisig_owner();
+            let transaction_id = self.next_transaction_id;
+            self.next_transaction_id = transaction_id.checked_add(1).expect("Arithmetic overflow in submit");
+            self.transactions.insert(transaction_id, action);
+            self.env().emit_event(TransactionSubmitted {
+                transaction_id,
+                submitter: self.env().caller(),
+            });
+            self.confirm_transaction(transaction_id);
+            transaction_id
+        }
+
+        #[ink(message)]
+        pub fn confirm_transaction(&mut self, transaction_id: u32) {
+            self.only_multisig_owner();
+            assert!(self.transactions.contains_key(&transaction_id), "Unknown transaction");
+            let owner = self.env().caller();
+            assert!(!self.confirmations.contains_key(&(transaction_id, owner)), "Transaction already confirmed");
+            self.confirmations.insert((transaction_id, owner), true);
+            self.env().emit_event(TransactionConfirmed { transaction_id, owner });
+        }
+
+        /// Carries out `transaction_id` once `threshold` owners confirmed it.
+        ///
+        /// While the timelock is enabled, collateral withdrawals and code upgrades
+        /// additionally have to be queued as the matching `TimelockAction` and
+        /// past their eta.
+        #[ink(message)]
+        pub fn execute_transaction(&mut self, transaction_id: u32) {
+            self.only_multisig_owner();
+            assert!(self.transactions.contains_key(&transaction_id), "Unknown transaction");
+            assert!(self.confirmation_count(transaction_id) >= self.threshold, "Not enough confirmations");
+            let action = self.transactions.take(&transaction_id).expect("Unknown transaction");
+            for owner in self.multisig_owners.clone() {
+                self.confirmations.take(&(transaction_id, owner));
+            }
+            let timelocked = match action {
+                MultisigAction::SetMaxSupply(_) => None,
+                MultisigAction::SetCodeHash(code_hash) => Some(TimelockAction::SetCodeHash(code_hash)),
+                MultisigAction::WithdrawCollateral(amount) => Some(TimelockAction::WithdrawCollateral(amount)),
+            };
+            let action_id = match timelocked {
+                Some(timelocked) if self.min_delay > 0 => Some(self.take_ready_action(timelocked)),
+                _ => None,
+            };
+
+            match action {
+                MultisigAction::SetMaxSupply(max_supply) => self.apply_max_supply(max_supply),
+                MultisigAction::SetCodeHash(code_hash) => self.apply_code_hash(code_hash),
+                MultisigAction::WithdrawCollateral(amount) => self.apply_withdraw_collateral(amount),
+            }
+
+            if let Some(action_id) = action_id {
+                self.env().emit_event(ActionExecuted {
+                    action_id: Hash::from(action_id),
+                });
+            }
+            self.env().emit_event(TransactionExecuted { transaction_id });
+        }
+
+        /// Removes `action` from the queue, trapping unless it was queued and its
+        /// eta has passed. Returns its `action_id`.
+        fn take_ready_action(&mut self, action: TimelockAction) -> [u8; 32] {
+            let action_id = self.action_id(action);
+            let eta = self.queued_actions.take(&action_id).expect("Action not queued");
+            assert!(self.env().block_timestamp() >= eta, "Action not ready");
+            action_id
+        }
+
+        /// Live price of the collateral, trapping if it is outside the price bounds.
+        /// Every consumer of the primary collateral price goes through here.
+        fn fetch_price(&self) -> u128 {
+            let price = self.live_price();
+            self.assert_price_in_bounds(price);
+            price
+        }
+
+        /// Live price of the collateral, falling back to the last known price if the
+        /// collateral contract cannot be queried. Not checked against the bounds.
+        fn live_price(&self) -> u128 {
+            if self.oracle.is_some() {
+                return self.collateral_price;
+            }
+            CollateralRef::from_account_id(self.collateral_address)
+                .price()
+                .unwrap_or(self.collateral_price)
+        }
+
+        /// Fetches and caches the live price of `token`, trapping if it is not an
+        /// accepted collateral type.
+        fn refresh_price(&mut self, token: AccountId) -> u128 {
+            if token == self.collateral_address {
+                let price = self.fetch_price();
+                if price != self.collateral_price {
+                    self.collateral_price = price;
+                    self.price_updated_at = self.env().block_timestamp();
+                }
+                let age = self.env().block_timestamp().saturating_sub(self.price_updated_at);
+                assert!(self.max_price_age == 0 || age <= self.max_price_age, "Price data is stale");
+                return price;
+            }
+            let live = CollateralRef::from_account_id(token).price();
+            let info = self.collaterals.get_mut(&token).expect("Unknown collateral type");
+            info.price = live.unwrap_or(info.price);
+            info.price
+        }
+
+        /// Traps if burning `amount` tokens for collateral would dip into the part
+        /// of the supply `native_reserve` backs 1:1 or debt positions back. Together
+        /// with `withdraw` and `repay` burning supply alongside what backs it, this
+        /// keeps the collateral pools, the native reserve and the positions each
+        /// backing their own share of the supply, whoever holds the tokens.
+        fn assert_collateral_backed(&self, amount: u128) {
+            assert!(amount <= self.pool_backed_supply(), "Tokens not backed by the collateral pools");
+        }
+
+        /// Part of the supply the collateral pools back, leaving out what the
+        /// native reserve and debt positions back.
+        fn pool_backed_supply(&self) -> u128 {
+            self.total_supply.saturating_sub(self.native_reserve).saturating_sub(self.total_debt)
+        }
+
+        fn assert_within_collateral_cap(&self, deposit: u128) {
+            assert!(deposit <= self.collateral_headroom(), "Collateral cap reached");
+        }
+
+        /// Traps unless the primary pool stays at or above `collateral_floor` after
+        /// `amount` is taken out of it.
+        fn assert_above_floor(&self, amount: u128) {
+            assert!(
+                self.collateral_pool.saturating_sub(amount) >= self.collateral_floor,
+                "Would breach collateral floor"
+            );
+        }
+
+        fn assert_price_in_bounds(&self, price: u128) {
+            assert!(price >= self.min_price && price <= self.max_price, "Price out of bounds");
+        }
+
+        fn set_collateral_pool(&mut self, token: AccountId, pool: u128) {
+            if token == self.collateral_address {
+                self.collateral_pool = pool;
+            } else if let Some(info) = self.collaterals.get_mut(&token) {
+                info.pool = pool;
+            }
+        }
+
+        /// Value of `collateral` at `price` relative to `debt`, in basis points.
+        fn health_of(&self, collateral: u128, debt: u128, price: u128) -> u128 {
+            if debt == 0 {
+                return u128::MAX;
+            }
+            let value = U256::from(collateral) * U256::from(price) * U256::from(BASIS_POINTS);
+            let health = value / (U256::from(debt) * U256::from(self.price_scale()));
+            u128::try_from(health).unwrap_or(u128::MAX)
+        }
+
+        /// Denominator the collateral price is expressed in, `10^price_decimals`.
+        fn price_scale(&self) -> u128 {
+            10u128.pow(u32::from(self.price_decimals))
+        }
+
+        /// Collateral amount backing `amount` tokens at `price`, taking the collateral
+        /// ratio into account and rounded as `rounding` says.
+        ///
+        /// Computed in `U256` so the intermediate product cannot overflow; returns
+        /// `None` if the result does not fit into a `u128`.
+        fn collateral_for(&self, amount: u128, price: u128, rounding: RoundingMode) -> Option<u128> {
+            mul_div_round(
+                U256::from(amount) * U256::from(price),
+                U256::from(self.collateral_ratio),
+                U256::from(BASIS_POINTS) * U256::from(self.price_scale()),
+                rounding,
+            )
+        }
+
+        #[ink(message)]
+        pub fn freeze(&mut self, account: AccountId) {
+            self.only_owner();
+            self.frozen.insert(account, true);
+            self.env().emit_event(AccountFrozen { account });
+        }
+
+        #[ink(message)]
+        pub fn unfreeze(&mut self, account: AccountId) {
+            self.only_owner();
+            self.frozen.take(&account);
+            self.env().emit_event(AccountUnfrozen { account });
+        }
+
+        /// Leaves `account`'s balance out of `circulating_supply`.
+        #[ink(message)]
+        pub fn add_non_circulating(&mut self, account: AccountId) {
+            self.only_owner();
+            if !self.non_circulating.contains(&account) {
+                self.non_circulating.push(account);
+            }
+        }
+
+        #[ink(message)]
+        pub fn remove_non_circulating(&mut self, account: AccountId) {
+            self.only_owner();
+            self.non_circulating.retain(|excluded| *excluded != account);
+        }
+
+        /// Limits transfers to whitelisted senders and recipients while enabled.
+        #[ink(message)]
+        pub fn set_whitelist_enabled(&mut self, whitelist_enabled: bool) {
+            self.only_owner();
+            self.whitelist_enabled = whitelist_enabled;
+        }
+
+        #[ink(message)]
+        pub fn add_to_whitelist(&mut self, account: AccountId) {
+            self.only_owner();
+            self.transfer_whitelist.insert(account, true);
+            self.env().emit_event(AddedToWhitelist { account });
+        }
+
+        #[ink(message)]
+        pub fn remove_from_whitelist(&mut self, account: AccountId) {
+            self.only_owner();
+            self.transfer_whitelist.take(&account);
+            self.env().emit_event(RemovedFromWhitelist { account });
+        }
+
+        /// Moves `amount` of `from`'s tokens to `to` without an allowance, and
+        /// regardless of pauses, freezes and transfer fees.
+        #[ink(message)]
+        pub fn force_transfer(&mut self, from: AccountId, to: AccountId, amount: u128) {
+            self.only_owner();
+            assert!(!Self::is_zero_address(&to), "Zero address not allowed");
+            let balance = self.balance_of(from);
+            assert!(balance >= amount, "Not enough balance");
+
+            self.set_balance(from, balance - amount);
+            let to_balance = self.balance_of(to).checked_add(amount).expect("Arithmetic overflow in transfer");
+            self.set_balance(to, to_balance);
+
+            self.env().emit_event(ForcedTransfer { from, to, amount });
+            self.env().emit_event(Transfer {
+                from,
+                to,
+                amount,
+                memo: self.transfer_memo(),
+            });
+        }
+
+        /// Moves the transfer and flash mint fees the fee collector accrued since the
+        /// last sweep to `to` and resets its `unswept_fees_of`. `fees_collected_by`
+        /// and `total_fees_collected` keep counting. Sweeping to the fee collector
+        /// itself moves nothing and returns `0`.
+        #[ink(message)]
+        pub fn sweep_fees(&mut self, to: AccountId) -> u128 {
+            self.only_owner();
+            assert!(!Self::is_zero_address(&to), "Zero address not allowed");
+            let collector = self.fee_collector;
+            if collector == to {
+                return 0;
+            }
+            let balance = self.balance_of(collector);
+            // The collector may have spent part of its fees already
+            let amount = self.unswept_fees_of(collector).min(balance);
+            self.unswept_fees.insert(collector, 0);
+            self.set_balance(collector, balance - amount);
+            let to_balance = self.balance_of(to).checked_add(amount).expect("Arithmetic overflow in transfer");
+            self.set_balance(to, to_balance);
+
+            self.env().emit_event(FeesSwept { from: collector, to, amount });
+            self.env().emit_event(Transfer {
+                from: collector,
+                to,
+                amount,
+                memo: self.transfer_memo(),
+            });
+            amount
+        }
+
+        #[ink(message)]
+        pub fn snapshot(&mut self) -> u128 {
+            self.only_owner();
+            self.current_snapshot_id += 1;
+            self.env().emit_event(Snapshot {
+                id: self.current_snapshot_id,
+            });
+            self.current_snapshot_id
+        }
+
+        /// Every balance change goes through here so snapshots stay accurate.
+        fn set_balance(&mut self, account: AccountId, balance: u128) {
+            self.settle_dividend(account);
+            let current = self.current_snapshot_id;
+            if current > 0 {
+                let previous = self.balance_of(account);
+                let snapshots = self.account_snapshots.entry(account).or_insert_with(Vec::new);
+                Self::update_snapshot(snapshots, current, previous);
+            }
+            let raw = mul_div(balance, SCALING_UNIT, self.scaling_factor).expect("Arithmetic overflow in rebase");
+            let previous_raw = self.balances.insert(account, raw).unwrap_or(0);
+            self.held_raw_supply = self
+                .held_raw_supply
+                .checked_sub(previous_raw)
+                .and_then(|supply| supply.checked_add(raw))
+                .expect("Arithmetic overflow in set_balance");
+            self.update_holders(account, raw > 0);
+        }
+
+        /// Adds `account` to or removes it from `holders` depending on whether it
+        /// now holds tokens.
+        fn update_holders(&mut self, account: AccountId, holding: bool) {
+            let index = self.holder_index.get(&account).cloned();
+            match (index, holding) {
+                (None, true) => {
+                    self.holder_index.insert(account, self.holders.len());
+                    self.holders.push(account);
+                }
+                (Some(index), false) => {
+                    self.holder_index.take(&account);
+                    self.holders.swap_remove_drop(index);
+                    if let Some(moved) = self.holders.get(index).cloned() {
+                        self.holder_index.insert(moved, index);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        /// Books the dividends `account` earned on its current balance, so they
+        /// survive the balance changing.
+        fn settle_dividend(&mut self, account: AccountId) {
+            if self.reward_per_token == 0 {
+                return;
+            }
+            let owed = self.claimable_dividend(account);
+            self.dividends_owed.insert(account, owed);
+            self.reward_debt.insert(account, self.reward_per_token);
+        }
+
+        /// Moves `account`'s accrued staking rewards into `unclaimed_rewards` and
+        /// restarts accrual from the current block.
+        fn settle_rewards(&mut self, account: AccountId) {
+            let pending = self.pending_rewards(account);
+            self.unclaimed_rewards.insert(account, pending);
+            self.stake_blocks.insert(account, self.env().block_number());
+        }
+
+        /// Every total supply change goes through here so snapshots stay accurate.
+        fn set_total_supply(&mut self, total_supply: u128) {
+            let current = self.current_snapshot_id;
+            let previous = self.total_supply;
+            Self::update_snapshot(&mut self.total_supply_snapshots, current, previous);
+            self.total_supply = total_supply;
+        }
+
+        /// Records `value` as of the current snapshot unless it already has a checkpoint.
+        fn update_snapshot(snapshots: &mut Vec<(u128, u128)>, current: u128, value: u128) {
+            if current > 0 && snapshots.last().map_or(true, |(id, _)| *id < current) {
+                snapshots.push((current, value));
+            }
+        }
+
+        /// The value at `snapshot_id` is held by the first checkpoint taken at or after
+        /// it; `None` means the value has not changed since.
+        fn value_at(snapshots: &[(u128, u128)], snapshot_id: u128) -> Option<u128> {
+            snapshots.iter().find(|(id, _)| *id >= snapshot_id).map(|(_, value)| *value)
+        }
+
+        fn assert_valid_snapshot(&self, snapshot_id: u128) {
+            assert!(snapshot_id > 0 && snapshot_id <= self.current_snapshot_id, "Invalid snapshot id");
+        }
+
+        /// Guards messages that call into the collateral contract against reentrancy.
+        ///
+        /// A trap rolls the flag back together with all other state, so only the
+        /// successful path needs to call `unlock`.
+        fn lock(&mut self) {
+            assert!(!self.locked, "Reentrant call");
+            self.locked = true;
+            #[cfg(test)]
+            mock::set_locked(true);
+        }
+
+        fn unlock(&mut self) {
+            self.locked = false;
+            #[cfg(test)]
+            mock::set_locked(false);
+        }
+
+        /// Default `Transfer` memo, linking the event to the caller and block.
+        fn transfer_memo(&self) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(self.env().caller(), self.env().block_number())))
+        }
+
+        /// The all-zero account used as the counterparty of mints and burns.
+        fn zero_address() -> AccountId {
+            AccountId::clear()
+        }
+
+        fn is_zero_address(account: &AccountId) -> bool {
+            account.is_clear()
+        }
+
+        #[cfg(not(test))]
+        fn is_contract(&self, account: AccountId) -> bool {
+            self.env().is_contract(&account)
+        }
+
+        #[cfg(test)]
+        fn is_contract(&self, account: AccountId) -> bool {
+            mock::is_contract(account)
+        }
+
+        fn record_fee(&mut self, collector: AccountId, fee: u128) {
+            self.total_fees_collected = self.total_fees_collected.saturating_add(fee);
+            let collected = self.fees_collected_by(collector).saturating_add(fee);
+            self.fees_collected.insert(collector, collected);
+            let unswept = self.unswept_fees_of(collector).saturating_add(fee);
+            self.unswept_fees.insert(collector, unswept);
+        }
+
+        fn emit_metadata_updated(&self) {
+            self.env().emit_event(MetadataUpdated {
+                name: self.name.clone(),
+                symbol: self.symbol.clone(),
+            });
+        }
+
+        /// Stores `spender`'s allowance over `owner`, remembering `spender` for `revoke_all`.
+        fn set_allowance(&mut self, owner: AccountId, spender: AccountId, amount: u128) {
+            self.allowed.insert((owner, spender), amount);
+            if amount > 0 {
+                let spenders = self.spenders.entry(owner).or_insert_with(Vec::new);
+                if !spenders.contains(&spender) {
+                    spenders.push(spender);
+                }
+            }
+        }
+
+        /// Returns `spender`'s allowance over `owner` if it covers `amount`. Traps if
+        /// the allowance expired.
+        fn check_allowance(&self, owner: AccountId, spender: AccountId, amount: u128) -> Result<u128> {
+            self.assert_allowance_live(owner, spender);
+            let allowance = self.allowance(owner, spender);
+            if allowance < amount {
+                return Err(Error::InsufficientAllowance);
+            }
+            Ok(allowance)
+        }
+
+        /// Takes `amount` out of `spender`'s allowance over `owner`, emitting the
+        /// allowance left. Every path consuming an allowance goes through here.
+        fn spend_allowance(&mut self, owner: AccountId, spender: AccountId, amount: u128) -> Result<()> {
+            let remaining = self.check_allowance(owner, spender, amount)? - amount;
+            self.set_allowance(owner, spender, remaining);
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                amount: remaining,
+            });
+            Ok(())
+        }
+
+        fn assert_not_timelocked(&self) {
+            assert!(self.min_delay == 0, "Action must go through the timelock");
+        }
+
+        fn assert_not_multisig(&self) {
+            assert!(self.multisig_owners.is_empty(), "Action must go through the multisig");
+        }
+
+        fn only_multisig_owner(&self) {
+            assert!(self.multisig_owners.contains(&self.env().caller()), "Caller is not a multisig owner");
+        }
+
+        fn assert_allowance_live(&self, owner: AccountId, spender: AccountId) {
+            assert!(self.env().block_timestamp() <= self.allowance_expiry(owner, spender), "Allowance expired");
+        }
+
+        fn only_owner(&self) {
+            assert!(self.env().caller() == self.owner, "Caller is not the owner");
+        }
+
+        fn only_role(&self, role: RoleId) {
+            assert!(self.has_role(role, self.env().caller()), "Caller is missing role");
+        }
+    }
+
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+        /// Lets indexers correlate transfers; see `transfer_with_memo`.
+        #[ink(topic)]
+        memo: Hash,
+    }
+
+    #[ink(event)]
+    pub struct Minted {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+        collateral: u128,
+    }
+
+    #[ink(event)]
+    pub struct Redeemed {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+        collateral: u128,
+    }
+
+    #[ink(event)]
+    pub struct Burned {
+        #[ink(topic)]
+        from: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RatioUpdated {
+        old_ratio: u128,
+        new_ratio: u128,
+    }
+
+    #[ink(event)]
+    pub struct AccountFrozen {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AccountUnfrozen {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Snapshot {
+        id: u128,
+    }
+
+    #[ink(event)]
+    pub struct FeeCollected {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        collector: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct RedeemFeeCollected {
+        #[ink(topic)]
+        from: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct Liquidation {
+        #[ink(topic)]
+        liquidator: AccountId,
+        #[ink(topic)]
+        account: AccountId,
+        amount: u128,
+        collateral: u128,
+    }
+
+    #[ink(event)]
+    pub struct CollateralDonated {
+        #[ink(topic)]
+        donor: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct CollateralWithdrawn {
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct RoleGranted {
+        #[ink(topic)]
+        role: RoleId,
+        #[ink(topic)]
+        account: AccountId,
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoleRevoked {
+        #[ink(topic)]
+        role: RoleId,
+        #[ink(topic)]
+        account: AccountId,
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct VestingCreated {
+        #[ink(topic)]
+        beneficiary: AccountId,
+        amount: u128,
+        start: u64,
+        duration: u64,
+    }
+
+    #[ink(event)]
+    pub struct VestingReleased {
+        #[ink(topic)]
+        beneficiary: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct CodeUpgraded {
+        #[ink(topic)]
+        code_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct FlashMint {
+        #[ink(topic)]
+        target: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct FlashMintRepaid {
+        #[ink(topic)]
+        target: AccountId,
+        amount: u128,
+        fee: u128,
+    }
+
+    #[ink(event)]
+    pub struct TokensRescued {
+        #[ink(topic)]
+        token: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct Rebase {
+        old_supply: u128,
+        new_supply: u128,
+    }
+
+    #[ink(event)]
+    pub struct Staked {
+        #[ink(topic)]
+        account: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct Unstaked {
+        #[ink(topic)]
+        account: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct RewardsClaimed {
+        #[ink(topic)]
+        account: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct DividendDistributed {
+        #[ink(topic)]
+        from: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct DividendClaimed {
+        #[ink(topic)]
+        account: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct CollateralTypeAdded {
+        #[ink(topic)]
+        token: AccountId,
+        price: u128,
+    }
+
+    #[ink(event)]
+    pub struct PriceUpdated {
+        old_price: u128,
+        new_price: u128,
+    }
+
+    #[ink(event)]
+    pub struct MetadataUpdated {
+        name: String,
+        symbol: String,
+    }
+
+    #[ink(event)]
+    pub struct ForcedTransfer {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct Deposited {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Withdrawn {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct PositionUpdated {
+        #[ink(topic)]
+        account: AccountId,
+        collateral: u128,
+        debt: u128,
+    }
+
+    #[ink(event)]
+    pub struct ActionQueued {
+        #[ink(topic)]
+        action_id: Hash,
+        eta: u64,
+    }
+
+    #[ink(event)]
+    pub struct ActionExecuted {
+        #[ink(topic)]
+        action_id: Hash,
+    }
+
+    #[ink(event)]
+    pub struct Migrated {
+        from: u16,
+        to: u16,
+    }
+
+    #[ink(event)]
+    pub struct CollateralSwapped {
+        #[ink(topic)]
+        old_collateral: AccountId,
+        #[ink(topic)]
+        new_collateral: AccountId,
+        amount_in: u128,
+        amount_out: u128,
+    }
+
+    #[ink(event)]
+    pub struct RedeemRequested {
+        #[ink(topic)]
+        account: AccountId,
+        amount: u128,
+        collateral: u128,
+    }
+
+    #[ink(event)]
+    pub struct RedemptionProcessed {
+        #[ink(topic)]
+        account: AccountId,
+        collateral: u128,
+    }
+
+    #[ink(event)]
+    pub struct YieldAccrued {
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct AddedToWhitelist {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RemovedFromWhitelist {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AutoPaused {
+        backing_ratio: u128,
+    }
+
+    #[ink(event)]
+    pub struct RedeemedCollateralClaimed {
+        #[ink(topic)]
+        account: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct TransactionSubmitted {
+        transaction_id: u32,
+        #[ink(topic)]
+        submitter: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct TransactionConfirmed {
+        transaction_id: u32,
+        #[ink(topic)]
+        owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct TransactionExecuted {
+        transaction_id: u32,
+    }
+
+    #[ink(event)]
+    pub struct FeesSwept {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+    }
+
+    /// Off-chain stand-ins for the contracts `Chest` calls into.
+    #[cfg(test)]
+    mod mock {
+        use super::AccountId;
+        use std::{cell::RefCell, collections::HashMap};
+
+        #[derive(Default)]
+        struct Token {
+            price: Option<u128>,
+            /// Balances are only enforced once a test seeds one with `set_balance`;
+            /// until then the token accepts every transfer.
+            balances: Option<HashMap<AccountId, u128>>,
+        }
+
+        thread_local! {
+            static TOKENS: RefCell<HashMap<AccountId, Token>> = RefCell::new(HashMap::new());
+            static CALLBACKS: RefCell<HashMap<AccountId, bool>> = RefCell::new(HashMap::new());
+            static CODE_HASH: RefCell<Option<[u8; 32]>> = RefCell::new(None);
+            static BLOCKED: RefCell<HashMap<AccountId, Vec<AccountId>>> = RefCell::new(HashMap::new());
+            static SWAPS: RefCell<HashMap<AccountId, u128>> = RefCell::new(HashMap::new());
+            static LOCKED: RefCell<bool> = RefCell::new(false);
+            static LOCKED_DURING_TRANSFER: RefCell<Option<bool>> = RefCell::new(None);
+        }
+
+        /// Mirrors `Chest::locked`, so the mock token can see it mid-call.
+        pub fn set_locked(locked: bool) {
+            LOCKED.with(|current| *current.borrow_mut() = locked);
+        }
+
+        /// Whether `Chest` held its reentrancy lock during the last token
+        /// transfer, `None` before any transfer.
+        pub fn locked_during_last_transfer() -> Option<bool> {
+            LOCKED_DURING_TRANSFER.with(|observed| *observed.borrow())
+        }
+
+        /// Makes the mock exchange at `contract` pay out `amount_out` for any swap.
+        pub fn set_swap_output(contract: AccountId, amount_out: u128) {
+            SWAPS.with(|swaps| swaps.borrow_mut().insert(contract, amount_out));
+        }
+
+        pub fn swap_output(contract: AccountId) -> u128 {
+            SWAPS.with(|swaps| swaps.borrow().get(&contract).copied().unwrap_or(0))
+        }
+
+        /// Makes the mock contract at `contract` reject calls concerning `account`.
+        pub fn block(contract: AccountId, account: AccountId) {
+            BLOCKED.with(|blocked| blocked.borrow_mut().entry(contract).or_default().push(account));
+        }
+
+        pub fn is_blocked(contract: AccountId, account: AccountId) -> bool {
+            BLOCKED.with(|blocked| blocked.borrow().get(&contract).map_or(false, |accounts| accounts.contains(&account)))
+        }
+
+        /// Deploys a mock contract at `contract` whose callbacks return `result`.
+        pub fn set_callback_result(contract: AccountId, result: bool) {
+            CALLBACKS.with(|callbacks| callbacks.borrow_mut().insert(contract, result));
+        }
+
+        pub fn is_contract(account: AccountId) -> bool {
+            CALLBACKS.with(|callbacks| callbacks.borrow().contains_key(&account))
+        }
+
+        /// Calling an account without a mock contract fails like a failed call would.
+        pub fn callback_result(contract: AccountId) -> bool {
+            CALLBACKS.with(|callbacks| callbacks.borrow().get(&contract).copied().unwrap_or(false))
+        }
+
+        pub fn set_code_hash(code_hash: [u8; 32]) {
+            CODE_HASH.with(|current| *current.borrow_mut() = Some(code_hash));
+        }
+
+        pub fn code_hash() -> Option<[u8; 32]> {
+            CODE_HASH.with(|current| *current.borrow())
+        }
+
+        pub fn set_price(token: AccountId, price: u128) {
+            TOKENS.with(|tokens| tokens.borrow_mut().entry(token).or_default().price = Some(price));
+        }
+
+        pub fn price(token: AccountId) -> Option<u128> {
+            TOKENS.with(|tokens| tokens.borrow().get(&token).and_then(|token| token.price))
+        }
+
+        pub fn set_balance(token: AccountId, account: AccountId, balance: u128) {
+            TOKENS.with(|tokens| {
+                tokens
+                    .borrow_mut()
+                    .entry(token)
+                    .or_default()
+                    .balances
+                    .get_or_insert_with(HashMap::new)
+                    .insert(account, balance);
+            });
+        }
+
+        pub fn balance_of(token: AccountId, account: AccountId) -> u128 {
+            TOKENS.with(|tokens| {
+                tokens
+                    .borrow()
+                    .get(&token)
+                    .and_then(|token| token.balances.as_ref())
+                    .and_then(|balances| balances.get(&account).copied())
+                    .unwrap_or(0)
+            })
+        }
+
+        pub fn transfer(token: AccountId, from: AccountId, to: AccountId, value: u128) -> bool {
+            let locked = LOCKED.with(|locked| *locked.borrow());
+            LOCKED_DURING_TRANSFER.with(|observed| *observed.borrow_mut() = Some(locked));
+            TOKENS.with(|tokens| {
+                let mut tokens = tokens.borrow_mut();
+                let balances = match tokens.entry(token).or_default().balances.as_mut() {
+                    Some(balances) => balances,
+                    None => return true,
+                };
+                let from_balance = balances.get(&from).copied().unwrap_or(0);
+                if from_balance < value {
+                    return false;
+                }
+                balances.insert(from, from_balance - value);
+                *balances.entry(to).or_insert(0) += value;
+                true
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn create_contract_works() {
+            let accounts =ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert_eq!(chest.name(), "Chest".to_string());
+            assert_eq!(chest.symbol(), "CHEST".to_string());
+            assert_eq!(chest.decimals(), 18);
+            assert_eq!(chest.total_supply(), 0);
+        }
+
+        #[test]
+        fn mint_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+            assert_eq!(chest.total_supply(), amount);
+            assert_eq!(chest.balance_of(accounts.alice), amount);
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is missing role")]
+        fn mint_not_minter() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.mint(100_000);
+        }
+
+        #[test]
+        fn transfer_ownership_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert_eq!(chest.owner(), accounts.alice);
+
+            chest.transfer_ownership(accounts.bob);
+            assert_eq!(chest.owner(), accounts.alice);
+            assert_eq!(chest.pending_owner(), Some(accounts.bob));
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.accept_ownership();
+            assert_eq!(chest.owner(), accounts.bob);
+            assert_eq!(chest.pending_owner(), None);
+            chest.set_max_supply(100_000);
+            assert_eq!(chest.max_supply(), 100_000);
+
+            for role in [ADMIN_ROLE, MINTER_ROLE, PAUSER_ROLE] {
+                assert!(!chest.has_role(role, accounts.alice));
+            }
+            assert!(chest.has_role(ADMIN_ROLE, accounts.bob));
+            chest.grant_role(MINTER_ROLE, accounts.bob);
+            assert!(chest.has_role(MINTER_ROLE, accounts.bob));
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the owner")]
+        fn transfer_ownership_revokes_previous_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.transfer_ownership(accounts.bob);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.accept_ownership();
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.set_max_supply(100_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the pending owner")]
+        fn accept_ownership_wrong_account() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.transfer_ownership(accounts.bob);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.charlie);
+            chest.accept_ownership();
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the pending owner")]
+        fn cancel_ownership_transfer_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.transfer_ownership(accounts.bob);
+            chest.cancel_ownership_transfer();
+            assert_eq!(chest.pending_owner(), None);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.accept_ownership();
+        }
+
+        #[test]
+        fn redeem_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+            chest.redeem(amount / 2);
+            assert_eq!(chest.total_supply(), amount / 2);
+            assert_eq!(chest.balance_of(accounts.alice), amount / 2);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough balance to redeem")]
+        fn redeem_not_enough_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+            chest.redeem(amount * 2);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough collateral in the pool")]
+        fn redeem_not_enough_collateral() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+            chest.redeem(amount);
+            chest.redeem(amount);
+        }
+
+        #[test]
+        #[should_panic(expected = "Arithmetic overflow in mint")]
+        fn mint_total_supply_overflow() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(u128::MAX - 1);
+            assert_eq!(chest.total_supply(), u128::MAX - 1);
+            chest.mint(2);
+        }
+
+        #[test]
+        #[should_panic(expected = "Arithmetic overflow in mint")]
+        fn mint_collateral_overflow() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 200, 2, 10_000, 0);
+            // The collateral needed is twice the amount and no longer fits into a u128
+            chest.mint(u128::MAX / 2 + 1);
+        }
+
+        #[test]
+        fn burn_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+            chest.burn(amount / 4);
+            assert_eq!(chest.total_supply(), amount - amount / 4);
+            assert_eq!(chest.balance_of(accounts.alice), amount - amount / 4);
+            // Burning leaves the collateral untouched
+            assert_eq!(chest.collateral_pool, amount);
+        }
+
+        #[test]
+        fn burn_zero_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+            chest.burn(0);
+            assert_eq!(chest.total_supply(), amount);
+            assert_eq!(chest.balance_of(accounts.alice), amount);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough balance to burn")]
+        fn burn_not_enough_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+            chest.burn(amount + 1);
+        }
+
+        #[test]
+        fn transfer_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+
+            // Transfer to Bob
+            assert_eq!(chest.transfer(accounts.bob, amount / 2), Ok(()));
+            assert_eq!(chest.balance_of(accounts.alice), amount / 2);
+            assert_eq!(chest.balance_of(accounts.bob), amount / 2);
+
+            // Transfer from Bob to Charlie on Bob's approval
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.approve(accounts.alice, amount / 4);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(chest.transfer_from(accounts.bob, accounts.charlie, amount / 4), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), amount / 4);
+            assert_eq!(chest.balance_of(accounts.charlie), amount / 4);
+            assert_eq!(chest.allowance(accounts.bob, accounts.alice), 0);
+        }
+
+        #[test]
+        fn increase_and_decrease_allowance_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+
+            chest.approve(accounts.bob, 100);
+            assert!(chest.increase_allowance(accounts.bob, 50));
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 150);
+
+            // Decreasing past zero clamps instead of underflowing
+            assert!(chest.decrease_allowance(accounts.bob, 200));
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 0);
+        }
+
+        #[test]
+        fn transfer_not_enough_allowance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+
+            assert_eq!(chest.transfer(accounts.bob, amount / 2), Ok(()));
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.approve(accounts.alice, amount / 4);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(
+                chest.transfer_from(accounts.bob, accounts.charlie, amount / 2),
+                Err(Error::InsufficientAllowance)
+            );
+            // Nothing moved and the allowance is intact
+            assert_eq!(chest.balance_of(accounts.bob), amount / 2);
+            assert_eq!(chest.allowance(accounts.bob, accounts.alice), amount / 4);
+        }
+
+        #[test]
+        fn transfer_not_enough_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+
+            assert_eq!(chest.transfer(accounts.bob, amount * 2), Err(Error::InsufficientBalance));
+            assert_eq!(chest.balance_of(accounts.alice), amount);
+            assert_eq!(chest.balance_of(accounts.bob), 0);
+        }
+
+        #[test]
+        fn transfer_from_not_enough_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+
+            chest.approve(accounts.bob, amount * 2);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(
+                chest.transfer_from(accounts.alice, accounts.charlie, amount * 2),
+                Err(Error::InsufficientBalance)
+            );
+            // A failed transfer does not consume the allowance
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), amount * 2);
+        }
+
+        #[test]
+        fn pause_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let amount = 100_000;
+            chest.mint(amount);
+
+            chest.pause();
+            assert!(chest.paused());
+            assert_eq!(chest.transfer(accounts.bob, amount / 2), Err(Error::ContractPaused));
+            assert_eq!(chest.balance_of(accounts.bob), 0);
+
+            chest.unpause();
+            assert!(!chest.paused());
+            assert_eq!(chest.transfer(accounts.bob, amount / 2), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), amount / 2);
+        }
+
+        #[test]
+        #[should_panic(expected = "Contract is paused")]
+        fn mint_paused() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.pause();
+            chest.mint(100_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Contract is paused")]
+        fn redeem_paused() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(100_000);
+            chest.pause();
+            chest.redeem(100_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is missing role")]
+        fn pause_not_pauser() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.pause();
+        }
+
+        #[test]
+        fn collateral_ratio_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert_eq!(chest.collateral_ratio(), 10_000);
+            chest.mint(1_000);
+            assert_eq!(chest.collateral_pool, 1_000);
+
+            // At 150% every minted token requires half again as much collateral
+            chest.set_collateral_ratio(15_000);
+            assert_eq!(chest.collateral_ratio(), 15_000);
+            chest.mint(1_000);
+            assert_eq!(chest.collateral_pool, 1_000 + 1_500);
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the owner")]
+        fn set_collateral_ratio_not_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.set_collateral_ratio(15_000);
+        }
+
+        #[test]
+        fn mint_uses_live_price() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_price(accounts.django, 200);
+            chest.mint(1_000);
+            assert_eq!(chest.collateral_pool, 2_000);
+            assert_eq!(chest.collateral_price, 200);
+
+            mock::set_price(accounts.django, 50);
+            chest.redeem(1_000);
+            assert_eq!(chest.collateral_pool, 1_500);
+        }
+
+        #[test]
+        fn fetch_price_falls_back_to_cached_price() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            assert_eq!(chest.fetch_price(), 100);
+            mock::set_price(accounts.django, 120);
+            assert_eq!(chest.fetch_price(), 120);
+        }
+
+        #[test]
+        fn mint_and_redeem_move_collateral() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+
+            chest.mint(1_000);
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_000);
+            assert_eq!(mock::balance_of(accounts.django, contract), 1_000);
+
+            chest.redeem(400);
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_400);
+            assert_eq!(mock::balance_of(accounts.django, contract), 600);
+            assert_eq!(chest.collateral_pool, 600);
+        }
+
+        #[test]
+        #[should_panic(expected = "Collateral transfer failed")]
+        fn mint_collateral_transfer_failed() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 500);
+            chest.mint(1_000);
+        }
+
+        #[test]
+        fn mint_to_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+
+            chest.mint_to(accounts.bob, 1_000);
+            assert_eq!(chest.balance_of(accounts.bob), 1_000);
+            assert_eq!(chest.balance_of(accounts.alice), 0);
+            assert_eq!(chest.total_supply(), 1_000);
+            // The caller pays the collateral
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_000);
+            assert_eq!(mock::balance_of(accounts.django, accounts.bob), 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "Max supply exceeded")]
+        fn mint_max_supply_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 1_000);
+            assert_eq!(chest.max_supply(), 1_000);
+            chest.mint(600);
+            chest.mint(400);
+            assert_eq!(chest.total_supply(), 1_000);
+            chest.mint(1);
+        }
+
+        #[test]
+        fn set_max_supply_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 1_000);
+            chest.mint(1_000);
+            chest.set_max_supply(2_000);
+            assert_eq!(chest.max_supply(), 2_000);
+            chest.mint(1_000);
+            assert_eq!(chest.total_supply(), 2_000);
+
+            // Lifting the cap entirely
+            chest.set_max_supply(0);
+            chest.mint(1_000);
+            assert_eq!(chest.total_supply(), 3_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Max supply below total supply")]
+        fn set_max_supply_below_total_supply() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_max_supply(999);
+        }
+
+        fn decode_events() -> Vec<Event> {
+            ink_env::test::recorded_events()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).expect("Invalid event data"))
+                .collect()
+        }
+
+        #[test]
+        fn mint_emits_transfer_from_zero() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint_to(accounts.bob, 1_000);
+
+            let events = decode_events();
+            assert_eq!(events.len(), 2);
+            match &events[0] {
+                Event::Minted(Minted { from, to, amount, .. }) => {
+                    assert_eq!((*from, *to, *amount), (accounts.alice, accounts.bob, 1_000));
+                }
+                _ => panic!("Expected a Minted event"),
+            }
+            match &events[1] {
+                Event::Transfer(Transfer { from, to, amount, .. }) => {
+                    assert_eq!((*from, *to, *amount), (Chest::zero_address(), accounts.bob, 1_000));
+                }
+                _ => panic!("Expected a Transfer event"),
+            }
+        }
+
+        #[test]
+        fn redeem_and_burn_emit_transfer_to_zero() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.redeem(300);
+            chest.burn(200);
+
+            let events = decode_events();
+            assert_eq!(events.len(), 6);
+            match &events[2] {
+                Event::Redeemed(Redeemed { from, amount, .. }) => {
+                    assert_eq!((*from, *amount), (accounts.alice, 300));
+                }
+                _ => panic!("Expected a Redeemed event"),
+            }
+            match &events[3] {
+                Event::Transfer(Transfer { from, to, amount, .. }) => {
+                    assert_eq!((*from, *to, *amount), (accounts.alice, Chest::zero_address(), 300));
+                }
+                _ => panic!("Expected a Transfer event"),
+            }
+            match &events[4] {
+                Event::Burned(Burned { from, amount }) => {
+                    assert_eq!((*from, *amount), (accounts.alice, 200));
+                }
+                _ => panic!("Expected a Burned event"),
+            }
+            match &events[5] {
+                Event::Transfer(Transfer { from, to, amount, .. }) => {
+                    assert_eq!((*from, *to, *amount), (accounts.alice, Chest::zero_address(), 200));
+                }
+                _ => panic!("Expected a Transfer event"),
+            }
+        }
+
+        #[test]
+        fn batch_transfer_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+
+            assert!(chest.batch_transfer(
+                vec![accounts.bob, accounts.charlie, accounts.django],
+                vec![100, 200, 300],
+            ));
+            assert_eq!(chest.balance_of(accounts.alice), 400);
+            assert_eq!(chest.balance_of(accounts.bob), 100);
+            assert_eq!(chest.balance_of(accounts.charlie), 200);
+            assert_eq!(chest.balance_of(accounts.django), 300);
+
+            // One Transfer per recipient after the two mint events
+            let events = decode_events();
+            assert_eq!(events.len(), 5);
+        }
+
+        #[test]
+        #[should_panic(expected = "Recipients and amounts length mismatch")]
+        fn batch_transfer_length_mismatch() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.batch_transfer(vec![accounts.bob, accounts.charlie], vec![100]);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough balance")]
+        fn batch_transfer_not_enough_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.batch_transfer(vec![accounts.bob, accounts.charlie], vec![600, 600]);
+        }
+
+        #[test]
+        fn freeze_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(accounts.bob, 500), Ok(()));
+
+            chest.freeze(accounts.bob);
+            assert!(chest.is_frozen(accounts.bob));
+            // Transfers to Bob fail
+            assert_eq!(chest.transfer(accounts.bob, 100), Err(Error::AccountFrozen));
+            // Transfers from Bob fail
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.transfer(accounts.alice, 100), Err(Error::AccountFrozen));
+            chest.approve(accounts.charlie, 100);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(chest.transfer_from(accounts.bob, accounts.charlie, 100), Err(Error::AccountFrozen));
+            assert_eq!(chest.balance_of(accounts.bob), 500);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.unfreeze(accounts.bob);
+            assert!(!chest.is_frozen(accounts.bob));
+            assert_eq!(chest.transfer(accounts.bob, 100), Ok(()));
+        }
+
+        #[test]
+        #[should_panic(expected = "Account is frozen")]
+        fn redeem_frozen() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.freeze(accounts.alice);
+            chest.redeem(1_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the owner")]
+        fn freeze_not_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.freeze(accounts.alice);
+        }
+
+        /// Account of the secp256k1 key that signed `PERMIT_SIGNATURE`.
+        const PERMIT_OWNER: [u8; 32] = [
+            0x92, 0x06, 0x98, 0x70, 0xc1, 0x85, 0xd3, 0x8a, 0xcc, 0x5b, 0xc1, 0x07, 0x68, 0xff, 0x56, 0x4e,
+            0x76, 0x76, 0x17, 0x6b, 0xa6, 0xdb, 0x19, 0xc0, 0x87, 0x53, 0xfa, 0x9a, 0x16, 0xbb, 0x65, 0xf1,
+        ];
+
+        /// Permit for Bob to spend 1000 tokens with nonce 0 and deadline 1000, signed
+        /// for a contract deployed at Eve's address.
+        const PERMIT_SIGNATURE: [u8; 65] = [
+            0xd4, 0x76, 0x44, 0x53, 0x9a, 0xce, 0xc3, 0xda, 0x5e, 0x3e, 0xcf, 0x5f, 0xe8, 0x86, 0x3c, 0x62,
+            0x8a, 0x9c, 0x97, 0xe8, 0xb7, 0x1e, 0x9e, 0xa9, 0x16, 0x7a, 0x6f, 0x4f, 0x83, 0xc0, 0x3c, 0x32,
+            0x48, 0xfe, 0xf5, 0x48, 0x70, 0x6f, 0x55, 0x26, 0xc2, 0x25, 0x45, 0x15, 0x65, 0x4e, 0x93, 0xa4,
+            0x0d, 0x8a, 0x38, 0xd5, 0x52, 0x8f, 0xac, 0xc5, 0xcf, 0x82, 0x79, 0xb1, 0x90, 0x77, 0x3a, 0x96,
+            0x00,
+        ];
+
+        /// Compressed public key of the key behind `PERMIT_OWNER`.
+        const ORACLE_PUBKEY: [u8; 33] = [
+            0x02, 0x4e, 0x3b, 0x81, 0xaf, 0x9c, 0x22, 0x34, 0xca, 0xd0, 0x9d, 0x67, 0x9c, 0xe6, 0x03, 0x5e,
+            0xd1, 0x39, 0x23, 0x47, 0xce, 0x64, 0xce, 0x40, 0x5f, 0x5d, 0xcd, 0x36, 0x22, 0x8a, 0x25, 0xde,
+            0x6e,
+        ];
+
+        /// Price of 1.50 at timestamp 1000, signed by `ORACLE_PUBKEY` for a contract
+        /// deployed at Eve's address.
+        const ORACLE_PRICE_SIGNATURE: [u8; 65] = [
+            0xd4, 0x76, 0x44, 0x53, 0x9a, 0xce, 0xc3, 0xda, 0x5e, 0x3e, 0xcf, 0x5f, 0xe8, 0x86, 0x3c, 0x62,
+            0x8a, 0x9c, 0x97, 0xe8, 0xb7, 0x1e, 0x9e, 0xa9, 0x16, 0x7a, 0x6f, 0x4f, 0x83, 0xc0, 0x3c, 0x32,
+            0x48, 0x80, 0xa8, 0xf1, 0xb0, 0x1c, 0xb1, 0x35, 0x48, 0x26, 0x2c, 0x83, 0x17, 0xe8, 0x6f, 0x81,
+            0x5c, 0x7b, 0x74, 0xf6, 0x3e, 0xa7, 0xa6, 0x09, 0x99, 0x4a, 0xe9, 0x41, 0x3e, 0xc3, 0xad, 0x52,
+            0x01,
+        ];
+
+        /// The same price signed by a different key.
+        const FOREIGN_PRICE_SIGNATURE: [u8; 65] = [
+            0xf3, 0x0e, 0x4b, 0xd8, 0x09, 0x4e, 0x53, 0xa6, 0x79, 0xdd, 0xb8, 0xf5, 0x5b, 0x52, 0x16, 0xb0,
+            0x3c, 0x44, 0x62, 0x3f, 0xc4, 0x27, 0x9e, 0xf0, 0x79, 0x1f, 0x9a, 0xa1, 0xf6, 0x93, 0x0d, 0x49,
+            0x09, 0xc8, 0x28, 0x98, 0xba, 0xd9, 0x0a, 0x67, 0xb8, 0x82, 0xe4, 0x49, 0xb9, 0x80, 0x03, 0xe6,
+            0x90, 0x0f, 0xee, 0x47, 0x38, 0x91, 0xc3, 0x27, 0xf6, 0xfe, 0x53, 0x12, 0x0b, 0x97, 0xe4, 0x7e,
+            0x00,
+        ];
+
+        #[test]
+        fn permit_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let owner = AccountId::from(PERMIT_OWNER);
+
+            chest.permit(owner, accounts.bob, 1_000, 1_000, PERMIT_SIGNATURE);
+            assert_eq!(chest.allowance(owner, accounts.bob), 1_000);
+            assert_eq!(chest.nonces(owner), 1);
+        }
+
+        #[test]
+        #[should_panic(expected = "Invalid signature")]
+        fn permit_replay() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            let owner = AccountId::from(PERMIT_OWNER);
+
+            chest.permit(owner, accounts.bob, 1_000, 1_000, PERMIT_SIGNATURE);
+            chest.permit(owner, accounts.bob, 1_000, 1_000, PERMIT_SIGNATURE);
+        }
+
+        #[test]
+        #[should_panic(expected = "Invalid signature")]
+        fn permit_wrong_value() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.permit(AccountId::from(PERMIT_OWNER), accounts.bob, 2_000, 1_000, PERMIT_SIGNATURE);
+        }
+
+        #[test]
+        #[should_panic(expected = "Permit expired")]
+        fn permit_expired() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(1_001);
+            chest.permit(AccountId::from(PERMIT_OWNER), accounts.bob, 1_000, 1_000, PERMIT_SIGNATURE);
+        }
+
+        #[test]
+        fn snapshot_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+
+            let first = chest.snapshot();
+            assert_eq!(first, 1);
+            assert_eq!(chest.transfer(accounts.bob, 400), Ok(()));
+            chest.mint(500);
+
+            let second = chest.snapshot();
+            assert_eq!(second, 2);
+            assert_eq!(chest.transfer(accounts.bob, 100), Ok(()));
+
+            assert_eq!(chest.balance_of_at(accounts.alice, first), 1_000);
+            assert_eq!(chest.balance_of_at(accounts.bob, first), 0);
+            assert_eq!(chest.total_supply_at(first), 1_000);
+
+            assert_eq!(chest.balance_of_at(accounts.alice, second), 1_100);
+            assert_eq!(chest.balance_of_at(accounts.bob, second), 400);
+            assert_eq!(chest.total_supply_at(second), 1_500);
+
+            assert_eq!(chest.balance_of(accounts.alice), 1_000);
+            assert_eq!(chest.balance_of(accounts.bob), 500);
+        }
+
+        #[test]
+        #[should_panic(expected = "Invalid snapshot id")]
+        fn balance_of_at_future_snapshot() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.snapshot();
+            chest.balance_of_at(accounts.alice, 2);
+        }
+
+        #[test]
+        fn transfer_fee_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(100_000);
+            chest.set_transfer_fee(100, accounts.eve);
+
+            assert_eq!(chest.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(chest.balance_of(accounts.alice), 90_000);
+            assert_eq!(chest.balance_of(accounts.bob), 9_900);
+            assert_eq!(chest.balance_of(accounts.eve), 100);
+            assert_eq!(chest.total_supply(), 100_000);
+
+            // The fee rounds down
+            assert_eq!(chest.transfer(accounts.bob, 199), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 9_900 + 198);
+            assert_eq!(chest.balance_of(accounts.eve), 101);
+        }
+
+        #[test]
+        fn zero_transfer_fee_collects_nothing() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(100_000);
+            assert_eq!(chest.fee_bps(), 0);
+
+            assert_eq!(chest.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 10_000);
+            // Two mint events plus a single Transfer
+            assert_eq!(decode_events().len(), 3);
+        }
+
+        #[test]
+        #[should_panic(expected = "Fee exceeds 100%")]
+        fn set_transfer_fee_too_high() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_transfer_fee(10_001, accounts.eve);
+        }
+
+        #[test]
+        fn collateralization_ratio_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            assert_eq!(chest.collateralization_ratio(), u128::MAX);
+            chest.mint(1_000);
+            assert_eq!(chest.collateralization_ratio(), 10_000);
+            mock::set_price(accounts.django, 80);
+            assert_eq!(chest.collateralization_ratio(), 8_000);
+        }
+
+        #[test]
+        fn liquidate_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.set_liquidation_params(10_000, 500);
+            chest.mint(1_000);
+            chest.approve(accounts.bob, 100);
+
+            // The price drop leaves the pool at 50% collateralization
+            mock::set_price(accounts.django, 50);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.liquidate(accounts.alice, 100);
+
+            // 100 tokens are backed by 50 collateral, plus the 5% bonus
+            assert_eq!(mock::balance_of(accounts.django, accounts.bob), 52);
+            assert_eq!(chest.collateral_pool, 1_000 - 52);
+            assert_eq!(chest.balance_of(accounts.alice), 900);
+            assert_eq!(chest.total_supply(), 900);
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "Collateral ratio is healthy")]
+        fn liquidate_healthy() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.liquidate(accounts.alice, 100);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough allowance")]
+        fn liquidate_without_allowance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            mock::set_price(accounts.django, 50);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.liquidate(accounts.alice, 100);
+        }
+
+        #[test]
+        #[should_panic(expected = "Price out of bounds")]
+        fn liquidate_price_out_of_bounds() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            chest.set_price_bounds(50, 200);
+            mock::set_price(accounts.django, 10);
+            chest.liquidate(accounts.alice, 100);
+        }
+
+        #[test]
+        fn withdraw_collateral_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            assert_eq!(chest.excess_collateral(), 0);
+
+            // At the new price the supply needs only 800 collateral as backing
+            mock::set_price(accounts.django, 80);
+            assert_eq!(chest.excess_collateral(), 200);
+            chest.withdraw_collateral(200);
+            assert_eq!(chest.collateral_pool, 800);
+            assert_eq!(chest.excess_collateral(), 0);
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_200);
+        }
+
+        #[test]
+        #[should_panic(expected = "Price out of bounds")]
+        fn withdraw_collateral_price_out_of_bounds() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            chest.set_price_bounds(50, 200);
+            // A bad tick would otherwise value the whole pool as excess
+            mock::set_price(accounts.django, 1);
+            chest.withdraw_collateral(900);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough excess collateral")]
+        fn withdraw_collateral_beyond_excess() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.withdraw_collateral(1);
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the owner")]
+        fn withdraw_collateral_not_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.withdraw_collateral(0);
+        }
+
+        #[test]
+        fn new_default_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let chest = Chest::new_default(accounts.django);
+            assert_eq!(chest.name(), "Chest".to_string());
+            assert_eq!(chest.symbol(), "CHEST".to_string());
+            assert_eq!(chest.decimals(), 18);
+            assert_eq!(chest.collateral_price, 100);
+            assert_eq!(chest.collateral_ratio(), 10_000);
+            assert_eq!(chest.max_supply(), 0);
+            assert_eq!(chest.total_supply(), 0);
+            assert_eq!(chest.owner(), accounts.alice);
+        }
+
+        #[test]
+        fn new_with_supply_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            let chest = Chest::new_with_supply("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0, 1_000);
+            assert_eq!(chest.total_supply(), 1_000);
+            assert_eq!(chest.balance_of(accounts.alice), 1_000);
+            assert_eq!(chest.collateral_pool, 1_000);
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Max supply exceeded")]
+        fn new_with_supply_above_cap() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            Chest::new_with_supply("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 500, 1_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Reentrant call")]
+        fn redeem_reentrant_call() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert!(!chest.locked);
+
+            // `redeem` holds the lock while the collateral contract runs
+            chest.redeem(100);
+            assert_eq!(mock::locked_during_last_transfer(), Some(true));
+            assert!(!chest.locked);
+
+            // The off-chain environment cannot dispatch the malicious collateral
+            // contract's callback, so recreate the state it would observe: a
+            // redeem still in flight while it calls back into `redeem`.
+            chest.lock();
+            chest.redeem(500);
+        }
+
+        #[test]
+        fn lifetime_counters_work() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(100);
+            chest.redeem(40);
+            assert_eq!(chest.total_minted(), 100);
+            assert_eq!(chest.total_redeemed(), 40);
+            assert_eq!(chest.total_supply(), 60);
+        }
+
+        #[test]
+        fn price_decimals_scaling_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            // 1.5 collateral per token, quoted with 6 decimals
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 1_500_000, 6, 10_000, 0);
+            assert_eq!(chest.price_decimals(), 6);
+
+            let one_token = 1_000_000_000_000_000_000;
+            chest.mint(one_token);
+            assert_eq!(chest.collateral_pool, 1_500_000_000_000_000_000);
+
+            chest.mint(3);
+            assert_eq!(chest.collateral_pool, 1_500_000_000_000_000_000 + 5);
+        }
+
+        #[test]
+        #[should_panic(expected = "Price decimals too large")]
+        fn price_decimals_too_large() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 39, 10_000, 0);
+        }
+
+        #[test]
+        fn grant_minter_role_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert!(chest.has_role(ADMIN_ROLE, accounts.alice));
+            assert!(chest.has_role(MINTER_ROLE, accounts.alice));
+            assert!(!chest.has_role(MINTER_ROLE, accounts.bob));
+
+            chest.grant_role(MINTER_ROLE, accounts.bob);
+            assert!(chest.has_role(MINTER_ROLE, accounts.bob));
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.mint(1_000);
+            assert_eq!(chest.balance_of(accounts.bob), 1_000);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.revoke_role(MINTER_ROLE, accounts.bob);
+            assert!(!chest.has_role(MINTER_ROLE, accounts.bob));
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is missing role")]
+        fn grant_role_not_admin() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.grant_role(MINTER_ROLE, accounts.bob);
+        }
+
+        #[test]
+        fn pauser_role_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.grant_role(PAUSER_ROLE, accounts.bob);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.pause();
+            assert!(chest.paused());
+        }
+
+        #[test]
+        fn burn_from_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint_to(accounts.bob, 1_000);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.approve(accounts.alice, 600);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.burn_from(accounts.bob, 400);
+
+            assert_eq!(chest.allowance(accounts.bob, accounts.alice), 200);
+            assert_eq!(chest.balance_of(accounts.bob), 600);
+            assert_eq!(chest.total_supply(), 600);
+            match decode_events().last() {
+                Some(Event::Transfer(Transfer { from, to, amount, .. })) => {
+                    assert_eq!((*from, *to, *amount), (accounts.bob, Chest::zero_address(), 400));
+                }
+                _ => panic!("Expected a Transfer event"),
+            }
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough allowance")]
+        fn burn_from_not_enough_allowance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint_to(accounts.bob, 1_000);
+            chest.burn_from(accounts.bob, 1);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough balance to burn")]
+        fn burn_from_not_enough_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint_to(accounts.bob, 1_000);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.approve(accounts.alice, 2_000);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.burn_from(accounts.bob, 1_001);
+        }
+
+        #[test]
+        fn vesting_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(1_000);
+            chest.create_vesting(accounts.bob, 1_000, 2_000, 1_000);
+            assert_eq!(chest.balance_of(accounts.alice), 0);
+            assert_eq!(chest.total_supply(), 1_000);
+            assert_eq!(chest.vested_amount(accounts.bob), 0);
+
+            // Halfway through, half is unlocked
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(2_500);
+            assert_eq!(chest.vested_amount(accounts.bob), 500);
+            assert_eq!(chest.releasable(accounts.bob), 500);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.release_vested();
+            assert_eq!(chest.balance_of(accounts.bob), 500);
+            assert_eq!(chest.releasable(accounts.bob), 0);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            assert_eq!(chest.releasable(accounts.bob), 500);
+            chest.release_vested();
+            assert_eq!(chest.balance_of(accounts.bob), 1_000);
+            assert_eq!(chest.vested_amount(accounts.bob), 1_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough balance to vest")]
+        fn create_vesting_not_enough_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.create_vesting(accounts.bob, 1_001, 0, 1_000);
+        }
+
+        #[test]
+        fn mint_keeps_min_collateral_ratio() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_min_collateral_ratio(9_000);
+            assert_eq!(chest.min_collateral_ratio(), 9_000);
+            chest.mint(1_000);
+            chest.mint(1_000);
+            assert_eq!(chest.collateralization_ratio(), 10_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Would breach min collateral ratio")]
+        fn mint_breaches_min_collateral_ratio() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_min_collateral_ratio(9_000);
+            chest.mint(1_000);
+
+            // After the drop the pool would sit at 72% once the new tokens exist
+            mock::set_price(accounts.django, 80);
+            chest.mint(1_000);
+        }
+
+        #[test]
+        fn transfer_with_memo_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer_with_memo(accounts.bob, 100, [0x2a; 32]), Ok(()));
+
+            let raw = ink_env::test::recorded_events().last().expect("No events recorded");
+            // Event signature plus the from, to and memo topics
+            assert_eq!(raw.topics.len(), 4);
+            match <Event as scale::Decode>::decode(&mut &raw.data[..]).expect("Invalid event data") {
+                Event::Transfer(Transfer { from, to, amount, memo }) => {
+                    assert_eq!((from, to, amount), (accounts.alice, accounts.bob, 100));
+                    assert_eq!(memo, Hash::from([0x2a; 32]));
+                }
+                _ => panic!("Expected a Transfer event"),
+            }
+        }
+
+        #[test]
+        fn transfer_default_memo() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(accounts.bob, 100), Ok(()));
+
+            let mut expected = [0u8; 32];
+            ink_env::hash_encoded::<Blake2x256, _>(&(accounts.alice, 0u32), &mut expected);
+            match decode_events().last() {
+                Some(Event::Transfer(Transfer { memo, .. })) => assert_eq!(*memo, Hash::from(expected)),
+                _ => panic!("Expected a Transfer event"),
+            }
+        }
+
+        #[test]
+        fn set_code_hash_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_code_hash([0x07; 32]);
+            assert_eq!(mock::code_hash(), Some([0x07; 32]));
+            match decode_events().last() {
+                Some(Event::CodeUpgraded(CodeUpgraded { code_hash })) => assert_eq!(*code_hash, Hash::from([0x07; 32])),
+                _ => panic!("Expected a CodeUpgraded event"),
+            }
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the owner")]
+        fn set_code_hash_not_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.set_code_hash([0x07; 32]);
+        }
+
+        #[test]
+        fn flash_mint_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_transfer_fee(0, accounts.eve);
+            chest.set_flash_fee(100);
+            // The borrower already holds enough to pay the fee
+            chest.mint_to(accounts.frank, 10);
+            mock::set_callback_result(accounts.frank, true);
+
+            chest.flash_mint(1_000, accounts.frank, Vec::new());
+            assert_eq!(chest.total_supply(), 10);
+            assert_eq!(chest.balance_of(accounts.frank), 0);
+            assert_eq!(chest.balance_of(accounts.eve), 10);
+        }
+
+        #[test]
+        #[should_panic(expected = "Flash mint not repaid")]
+        fn flash_mint_not_repaid() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_flash_fee(100);
+            mock::set_callback_result(accounts.frank, true);
+            chest.flash_mint(1_000, accounts.frank, Vec::new());
+        }
+
+        #[test]
+        #[should_panic(expected = "Flash mint callback failed")]
+        fn flash_mint_callback_failed() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            mock::set_callback_result(accounts.frank, false);
+            chest.flash_mint(1_000, accounts.frank, Vec::new());
+        }
+
+        #[test]
+        #[should_panic(expected = "Arithmetic overflow in flash mint")]
+        fn flash_mint_fee_overflow() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_flash_fee(100);
+            mock::set_callback_result(accounts.frank, true);
+            chest.flash_mint(u128::MAX, accounts.frank, Vec::new());
+        }
+
+        #[test]
+        fn rescue_tokens_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.frank, contract, 500);
+
+            chest.rescue_tokens(accounts.frank, accounts.bob, 200);
+            assert_eq!(mock::balance_of(accounts.frank, contract), 300);
+            assert_eq!(mock::balance_of(accounts.frank, accounts.bob), 200);
+        }
+
+        #[test]
+        #[should_panic(expected = "Cannot rescue collateral")]
+        fn rescue_collateral_token() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.rescue_tokens(accounts.django, accounts.alice, 1_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the owner")]
+        fn rescue_tokens_not_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.rescue_tokens(accounts.frank, accounts.bob, 200);
+        }
+
+        #[test]
+        fn account_info_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.approve(accounts.bob, 300);
+            chest.freeze(accounts.alice);
+
+            assert_eq!(
+                chest.account_info(accounts.alice, accounts.bob),
+                (
+                    chest.balance_of(accounts.alice),
+                    chest.allowance(accounts.alice, accounts.bob),
+                    chest.is_frozen(accounts.alice),
+                )
+            );
+            assert_eq!(chest.account_info(accounts.alice, accounts.bob), (1_000, 300, true));
+        }
+
+        #[test]
+        fn mint_rate_limit_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_rate_limits(1_000, 0);
+            chest.mint(400);
+            chest.mint(600);
+            assert_eq!(chest.minted_in_current_block(), 1_000);
+
+            // The budget resets with the next block
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            assert_eq!(chest.minted_in_current_block(), 0);
+            chest.mint(1_000);
+            assert_eq!(chest.total_supply(), 2_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Mint rate limit exceeded")]
+        fn mint_rate_limit_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_rate_limits(1_000, 0);
+            chest.mint(400);
+            chest.mint(400);
+            chest.mint(201);
+        }
+
+        #[test]
+        #[should_panic(expected = "Redeem rate limit exceeded")]
+        fn redeem_rate_limit_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_rate_limits(0, 500);
+            chest.mint(1_000);
+            chest.redeem(300);
+            assert_eq!(chest.redeemed_in_current_block(), 300);
+            chest.redeem(201);
+        }
+
+        #[test]
+        fn rebase_scales_every_balance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(accounts.bob, 500), Ok(()));
+
+            chest.rebase(1_100);
+            assert_eq!(chest.total_supply(), 1_100);
+            assert_eq!(chest.balance_of(accounts.alice), 550);
+            assert_eq!(chest.balance_of(accounts.bob), 550);
+            assert_eq!(chest.scaling_factor(), 1_100_000_000_000_000_000);
+            // Raw storage is untouched
+            assert_eq!(*chest.balances.get(&accounts.alice).unwrap(), 500);
+            assert_eq!(*chest.balances.get(&accounts.bob).unwrap(), 500);
+
+            assert_eq!(chest.transfer(accounts.bob, 110), Ok(()));
+            assert_eq!(chest.balance_of(accounts.alice), 440);
+            assert_eq!(chest.balance_of(accounts.bob), 660);
+
+            let events = decode_events();
+            assert!(matches!(
+                events.last(),
+                Some(Event::Transfer(Transfer { amount: 110, .. }))
+            ));
+            assert!(events.iter().any(|event| matches!(event, Event::Rebase(Rebase { old_supply: 1_000, new_supply: 1_100 }))));
+        }
+
+        #[test]
+        fn rebase_keeps_snapshot_balances() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            let snapshot_id = chest.snapshot();
+
+            chest.rebase(2_000);
+            assert_eq!(chest.balance_of(accounts.alice), 2_000);
+            assert_eq!(chest.balance_of_at(accounts.alice, snapshot_id), 1_000);
+            assert_eq!(chest.total_supply_at(snapshot_id), 1_000);
+        }
+
+        #[test]
+        fn rebase_scales_staked_and_vested() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.stake(400);
+            chest.create_vesting(accounts.bob, 200, 0, 1_000);
+
+            chest.rebase(2_000);
+            assert_eq!(chest.balance_of(accounts.alice), 800);
+            assert_eq!(chest.staked_of(accounts.alice), 800);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(1_000);
+            assert_eq!(chest.releasable(accounts.bob), 400);
+
+            chest.unstake(800);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.release_vested();
+            assert_eq!(chest.balance_of(accounts.alice) + chest.balance_of(accounts.bob), chest.total_supply());
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the owner")]
+        fn rebase_only_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.rebase(900);
+        }
+
+        #[test]
+        fn staking_accrues_rewards() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_reward_rate(10);
+
+            chest.stake(600);
+            assert_eq!(chest.balance_of(accounts.alice), 400);
+            assert_eq!(chest.staked_of(accounts.alice), 600);
+            assert_eq!(chest.total_supply(), 1_000);
+
+            for _ in 0..5 {
+                ink_env::test::advance_block::<ink_env::DefaultEnvironment>();
+            }
+            // 600 * 10 bps * 5 blocks
+            assert_eq!(chest.pending_rewards(accounts.alice), 3);
+
+            chest.claim_rewards();
+            assert_eq!(chest.pending_rewards(accounts.alice), 0);
+            assert_eq!(chest.balance_of(accounts.alice), 403);
+            assert_eq!(chest.total_supply(), 1_003);
+
+            chest.unstake(600);
+            assert_eq!(chest.staked_of(accounts.alice), 0);
+            assert_eq!(chest.balance_of(accounts.alice), 1_003);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough staked")]
+        fn unstake_more_than_staked() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.stake(100);
+            chest.unstake(101);
+        }
+
+        #[test]
+        fn transfer_to_zero_address_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(AccountId::default(), 100), Err(Error::ZeroAddress));
+            assert_eq!(chest.balance_of(accounts.alice), 1_000);
+        }
+
+        #[test]
+        fn transfer_from_to_zero_address_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.approve(accounts.bob, 100);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.transfer_from(accounts.alice, AccountId::default(), 100), Err(Error::ZeroAddress));
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 100);
+        }
+
+        #[test]
+        #[should_panic(expected = "Zero address not allowed")]
+        fn mint_to_zero_address_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint_to(AccountId::default(), 1_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Zero address not allowed")]
+        fn increase_allowance_to_zero_address_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.increase_allowance(AccountId::default(), 100);
+        }
+
+        #[test]
+        #[should_panic(expected = "Zero address not allowed")]
+        fn permit_to_zero_address_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.permit(AccountId::from(PERMIT_OWNER), AccountId::default(), 1_000, 1_000, PERMIT_SIGNATURE);
+        }
+
+        #[test]
+        fn redeem_fee_stays_in_pool() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.set_redeem_fee(200);
+
+            chest.mint(1_000);
+            chest.redeem(500);
+            // 2% of the 500 collateral is retained
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_490);
+            assert_eq!(mock::balance_of(accounts.django, contract), 510);
+            assert_eq!(chest.collateral_pool, 510);
+            assert_eq!(chest.total_supply(), 500);
+
+            assert!(decode_events()
+                .iter()
+                .any(|event| matches!(event, Event::RedeemFeeCollected(RedeemFeeCollected { amount: 10, .. }))));
+        }
+
+        #[test]
+        fn dividends_are_split_pro_rata() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(accounts.bob, 250), Ok(()));
+
+            chest.distribute(1_000);
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 8_000);
+            assert_eq!(chest.claimable_dividend(accounts.alice), 750);
+            assert_eq!(chest.claimable_dividend(accounts.bob), 250);
+
+            // Moving tokens after the distribution keeps what was already earned
+            assert_eq!(chest.transfer(accounts.bob, 250), Ok(()));
+            assert_eq!(chest.claimable_dividend(accounts.alice), 750);
+            assert_eq!(chest.claimable_dividend(accounts.bob), 250);
+
+            chest.claim_dividend();
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 8_750);
+            assert_eq!(chest.claimable_dividend(accounts.alice), 0);
+            chest.claim_dividend();
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 8_750);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.claim_dividend();
+            assert_eq!(mock::balance_of(accounts.django, accounts.bob), 250);
+            assert_eq!(chest.claimable_dividend(accounts.bob), 0);
+        }
+
+        #[test]
+        fn dividends_skip_staked_and_vesting_tokens() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(accounts.bob, 250), Ok(()));
+            chest.stake(200);
+            chest.create_vesting(accounts.charlie, 50, 0, 1_000);
+
+            chest.distribute(750);
+            assert_eq!(chest.claimable_dividend(accounts.alice), 500);
+            assert_eq!(chest.claimable_dividend(accounts.bob), 250);
+        }
+
+        #[test]
+        fn dividends_survive_rebase() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(accounts.bob, 250), Ok(()));
+
+            chest.distribute(1_000);
+            chest.rebase(2_000);
+            assert_eq!(chest.claimable_dividend(accounts.alice), 750);
+            assert_eq!(chest.claimable_dividend(accounts.bob), 250);
+
+            chest.distribute(1_000);
+            assert_eq!(chest.claimable_dividend(accounts.alice), 1_500);
+            assert_eq!(chest.claimable_dividend(accounts.bob), 500);
+        }
+
+        #[test]
+        fn holders_are_tracked() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(accounts.bob, 300), Ok(()));
+            assert_eq!(chest.transfer(accounts.charlie, 200), Ok(()));
+            assert_eq!(chest.holders_count(), 3);
+            assert_eq!(chest.holders_page(0, 10), vec![accounts.alice, accounts.bob, accounts.charlie]);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.transfer(accounts.alice, 300), Ok(()));
+            assert_eq!(chest.holders_count(), 2);
+            assert_eq!(chest.holders_page(0, 10), vec![accounts.alice, accounts.charlie]);
+            assert_eq!(chest.holders_page(1, 1), vec![accounts.charlie]);
+            assert_eq!(chest.holders_page(2, 1), Vec::<AccountId>::new());
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            assert_eq!(chest.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(chest.holders_page(0, 10), vec![accounts.alice, accounts.charlie, accounts.bob]);
+        }
+
+        #[test]
+        fn safe_transfer_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            mock::set_callback_result(accounts.frank, true);
+
+            assert_eq!(chest.safe_transfer(accounts.frank, 300, Vec::new()), Ok(()));
+            assert_eq!(chest.balance_of(accounts.frank), 300);
+            // Plain accounts are not called
+            assert_eq!(chest.safe_transfer(accounts.bob, 200, Vec::new()), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 200);
+            assert_eq!(chest.balance_of(accounts.alice), 500);
+        }
+
+        #[test]
+        #[should_panic(expected = "Recipient rejected transfer")]
+        fn safe_transfer_rejected() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            mock::set_callback_result(accounts.frank, false);
+            let _ = chest.safe_transfer(accounts.frank, 300, Vec::new());
+        }
+
+        #[test]
+        fn approve_with_expiry_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(1_000);
+            assert!(chest.approve_with_expiry(accounts.bob, 500, 2_000));
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 500);
+            assert_eq!(chest.allowance_expiry(accounts.alice, accounts.bob), 2_000);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.transfer_from(accounts.alice, accounts.bob, 200), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 200);
+
+            // A plain approval clears the expiry
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.approve(accounts.bob, 500);
+            assert_eq!(chest.allowance_expiry(accounts.alice, accounts.bob), u64::MAX);
+        }
+
+        #[test]
+        #[should_panic(expected = "Allowance expired")]
+        fn transfer_from_expired_allowance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(1_000);
+            chest.approve_with_expiry(accounts.bob, 500, 2_000);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.transfer_from(accounts.alice, accounts.bob, 200), Ok(()));
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(2_001);
+            let _ = chest.transfer_from(accounts.alice, accounts.bob, 200);
+        }
+
+        #[test]
+        fn mint_with_multiple_collateral_types() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            mock::set_balance(accounts.frank, accounts.alice, 10_000);
+            mock::set_balance(accounts.eve, accounts.alice, 10_000);
+            chest.add_collateral_type(accounts.frank, 200);
+            chest.add_collateral_type(accounts.eve, 50);
+
+            chest.mint_with(accounts.django, 1_000);
+            chest.mint_with(accounts.frank, 1_000);
+            chest.mint_with(accounts.eve, 1_000);
+            assert_eq!(chest.total_supply(), 3_000);
+            assert_eq!(chest.collateral_pool_of(accounts.django), 1_000);
+            assert_eq!(chest.collateral_pool_of(accounts.frank), 2_000);
+            assert_eq!(chest.collateral_pool_of(accounts.eve), 500);
+            assert_eq!(mock::balance_of(accounts.frank, contract), 2_000);
+
+            chest.redeem_with(accounts.frank, 500);
+            assert_eq!(chest.collateral_pool_of(accounts.frank), 1_000);
+            assert_eq!(mock::balance_of(accounts.frank, accounts.alice), 9_000);
+            assert_eq!(chest.collateral_pool_of(accounts.django), 1_000);
+            assert_eq!(chest.total_supply(), 2_500);
+        }
+
+        #[test]
+        #[should_panic(expected = "Unknown collateral type")]
+        fn mint_with_unknown_collateral() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.mint_with(accounts.frank, 1_000);
+        }
+
+        #[test]
+        fn update_price_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_max_price_age(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.update_price(200);
+            assert_eq!(chest.price_updated_at(), 5_000);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_000);
+            chest.mint(1_000);
+            assert_eq!(chest.collateral_pool, 2_000);
+            assert!(matches!(
+                decode_events().first(),
+                Some(Event::PriceUpdated(PriceUpdated { old_price: 100, new_price: 200 }))
+            ));
+        }
+
+        #[test]
+        #[should_panic(expected = "Price data is stale")]
+        fn mint_with_stale_price() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_max_price_age(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.update_price(200);
+            chest.mint(1_000);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_001);
+            chest.mint(1_000);
+        }
+
+        #[test]
+        fn mint_cooldown_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_mint_cooldown(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.mint(100);
+            assert_eq!(chest.next_mint_time(accounts.alice), 6_000);
+            // Other recipients are unaffected
+            chest.mint_to(accounts.bob, 100);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_000);
+            chest.mint(100);
+            assert_eq!(chest.balance_of(accounts.alice), 200);
+        }
+
+        #[test]
+        #[should_panic(expected = "Mint cooldown active")]
+        fn mint_during_cooldown() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_mint_cooldown(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.mint(100);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_999);
+            chest.mint(100);
+        }
+
+        #[test]
+        fn backing_ratio_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 250, 2, 15_000, 0);
+            assert_eq!(chest.collateral_value(), 0);
+            assert_eq!(chest.backing_ratio_bps(), u128::MAX);
+
+            chest.mint(1_000);
+            // 1_000 * 2.50 * 150% of collateral, worth 3_750 * 2.50
+            assert_eq!(chest.collateral_pool, 3_750);
+            assert_eq!(chest.collateral_value(), 9_375);
+            assert_eq!(chest.backing_ratio_bps(), 93_750);
+        }
+
+        #[test]
+        fn batch_transfer_from_preview_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.approve(accounts.bob, 500);
+
+            assert_eq!(chest.batch_transfer_from_preview(accounts.alice, accounts.bob, vec![100, 200]), (true, 200));
+            assert_eq!(chest.batch_transfer_from_preview(accounts.alice, accounts.bob, vec![300, 200]), (true, 0));
+            assert_eq!(chest.batch_transfer_from_preview(accounts.alice, accounts.bob, vec![300, 201]), (false, 0));
+            assert_eq!(chest.batch_transfer_from_preview(accounts.alice, accounts.bob, vec![u128::MAX, 1]), (false, 500));
+
+            // The balance has to cover the batch as well
+            chest.approve(accounts.bob, 2_000);
+            assert_eq!(chest.batch_transfer_from_preview(accounts.alice, accounts.bob, vec![1_500]), (false, 500));
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 2_000);
+        }
+
+        #[test]
+        fn donate_collateral_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            mock::set_balance(accounts.django, accounts.bob, 10_000);
+            chest.mint(1_000);
+            assert_eq!(chest.backing_ratio_bps(), 10_000);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.donate_collateral(500);
+            assert_eq!(chest.collateral_pool, 1_500);
+            assert_eq!(mock::balance_of(accounts.django, contract), 1_500);
+            assert_eq!(mock::balance_of(accounts.django, accounts.bob), 9_500);
+            assert_eq!(chest.backing_ratio_bps(), 15_000);
+            assert_eq!(chest.total_supply(), 1_000);
+            assert_eq!(chest.balance_of(accounts.bob), 0);
+        }
+
+        #[test]
+        fn set_metadata_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_name("Vault".to_string());
+            chest.set_symbol("VLT".to_string());
+            assert_eq!(chest.name(), "Vault");
+            assert_eq!(chest.symbol(), "VLT");
+            assert_eq!(chest.decimals(), 18);
+            assert_eq!(decode_events().len(), 2);
+        }
+
+        #[test]
+        #[should_panic(expected = "Invalid symbol")]
+        fn set_symbol_empty() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_symbol(String::new());
+        }
+
+        #[test]
+        #[should_panic(expected = "Invalid name")]
+        fn set_name_too_long() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_name("x".repeat(65));
+        }
+
+        #[test]
+        fn force_transfer_from_frozen_account() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint_to(accounts.bob, 1_000);
+            chest.freeze(accounts.bob);
+            chest.pause();
+
+            chest.force_transfer(accounts.bob, accounts.charlie, 400);
+            assert_eq!(chest.balance_of(accounts.bob), 600);
+            assert_eq!(chest.balance_of(accounts.charlie), 400);
+
+            let events = decode_events();
+            assert!(events.iter().any(|event| matches!(
+                event,
+                Event::ForcedTransfer(ForcedTransfer { from, to, amount: 400 }) if *from == accounts.bob && *to == accounts.charlie
+            )));
+            assert!(matches!(events.last(), Some(Event::Transfer(Transfer { amount: 400, .. }))));
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the owner")]
+        fn force_transfer_not_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint_to(accounts.bob, 1_000);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.charlie);
+            chest.force_transfer(accounts.bob, accounts.charlie, 400);
+        }
+
+        #[test]
+        fn deposit_and_withdraw_native() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(1_000);
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 1_000).expect("Cannot set balance");
+            chest.deposit();
+            assert_eq!(chest.balance_of(accounts.bob), 1_000);
+            assert_eq!(chest.total_supply(), 1_000);
+            assert_eq!(chest.native_reserve(), 1_000);
+
+            let before = ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob).expect("Cannot get balance");
+            chest.withdraw(400);
+            assert_eq!(chest.balance_of(accounts.bob), 600);
+            assert_eq!(chest.total_supply(), 600);
+            assert_eq!(chest.native_reserve(), 600);
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob).expect("Cannot get balance"),
+                before + 400
+            );
+        }
+
+        #[test]
+        #[should_panic(expected = "Tokens not backed by the collateral pools")]
+        fn native_deposit_redeemed_for_collateral() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(100);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(1_000);
+            chest.deposit();
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(0);
+
+            assert_eq!(chest.collateralization_ratio(), 10_000);
+            assert_eq!(chest.backing_ratio_bps(), 10_000);
+
+            // Only the 100 collateral-backed tokens can be redeemed for collateral
+            chest.redeem(100);
+            assert_eq!(chest.collateral_pool, 0);
+            chest.redeem(1);
+        }
+
+        #[test]
+        fn native_and_collateral_backing_stay_apart() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(500);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(500);
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 500).expect("Cannot set balance");
+            chest.deposit();
+            ink_env::test::set_value_transferred::<ink_env::DefaultEnvironment>(0);
+
+            // The collateral minter takes the native side, leaving the depositor's
+            // tokens backed by the untouched collateral pool
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.withdraw(500);
+            assert_eq!(chest.native_reserve(), 0);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.redeem(500);
+            assert_eq!(chest.collateral_pool, 0);
+            assert_eq!(mock::balance_of(accounts.django, accounts.bob), 500);
+            assert_eq!(chest.total_supply(), 0);
+        }
+
+        #[test]
+        fn slippage_limits_within_bounds() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.mint_with_max_collateral(1_000, 1_000);
+            chest.redeem_with_min_collateral(500, 500);
+            assert_eq!(chest.total_supply(), 500);
+            assert_eq!(chest.collateral_pool, 500);
+        }
+
+        #[test]
+        #[should_panic(expected = "Slippage exceeded")]
+        fn mint_slippage_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_price(accounts.django, 110);
+            chest.mint_with_max_collateral(1_000, 1_050);
+        }
+
+        #[test]
+        #[should_panic(expected = "Slippage exceeded")]
+        fn redeem_slippage_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            mock::set_price(accounts.django, 90);
+            chest.redeem_with_min_collateral(500, 480);
+        }
+
+        #[test]
+        fn fee_accounting_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(10_000);
+            chest.set_transfer_fee(100, accounts.eve);
+            assert_eq!(chest.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(chest.transfer(accounts.bob, 2_000), Ok(()));
+            chest.set_transfer_fee(100, accounts.frank);
+            assert_eq!(chest.transfer(accounts.bob, 500), Ok(()));
+            assert_eq!(chest.fees_collected_by(accounts.eve), 30);
+            assert_eq!(chest.fees_collected_by(accounts.frank), 5);
+
+            chest.set_redeem_fee(200);
+            chest.redeem(1_000);
+            assert_eq!(chest.fees_collected_by(contract), 20);
+            assert_eq!(chest.total_fees_collected(), 55);
+        }
+
+        #[test]
+        fn self_transfer_is_a_no_op() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_transfer_fee(100, accounts.eve);
+            let events = decode_events().len();
+
+            assert_eq!(chest.transfer(accounts.alice, 500), Ok(()));
+            assert_eq!(chest.balance_of(accounts.alice), 1_000);
+            assert_eq!(chest.balance_of(accounts.eve), 0);
+            assert_eq!(decode_events().len(), events);
+
+            assert_eq!(chest.transfer(accounts.alice, 1_001), Err(Error::InsufficientBalance));
+        }
+
+        #[test]
+        fn mint_returns_collateral_charged() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            assert_eq!(chest.mint(1_000), 1_000);
+            mock::set_price(accounts.django, 125);
+            // 1_000 * 1.25
+            assert_eq!(chest.mint_to(accounts.bob, 1_000), 1_250);
+            assert_eq!(chest.collateral_pool, 2_250);
+        }
+
+        #[test]
+        fn transfer_units_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(5_000_000_000_000_000_000);
+
+            assert!(chest.transfer_units(accounts.bob, 1, 0));
+            assert_eq!(chest.balance_of(accounts.bob), 1_000_000_000_000_000_000);
+            assert!(chest.transfer_units(accounts.bob, 1, 500_000_000_000_000_000));
+            assert_eq!(chest.balance_of(accounts.bob), 2_500_000_000_000_000_000);
+            assert_eq!(chest.balance_of(accounts.alice), 2_500_000_000_000_000_000);
+
+            assert!(!chest.transfer_units(accounts.bob, 3, 0));
+        }
+
+        #[test]
+        #[should_panic(expected = "Fractional part too large")]
+        fn transfer_units_fraction_too_large() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(5_000_000_000_000_000_000);
+            chest.transfer_units(accounts.bob, 1, 1_000_000_000_000_000_000);
+        }
+
+        #[test]
+        fn open_position_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.set_position_min_ratio(5_000);
+
+            chest.open_position(1_000, 1_500);
+            assert_eq!(chest.balance_of(accounts.alice), 1_500);
+            assert_eq!(chest.debt_of(accounts.alice), 1_500);
+            assert_eq!(chest.position_collateral_of(accounts.alice), 1_000);
+            assert_eq!(chest.position_health(accounts.alice), 6_666);
+            assert_eq!(mock::balance_of(accounts.django, contract), 1_000);
+            assert_eq!(chest.collateral_pool, 0);
+
+            chest.repay(500);
+            assert_eq!(chest.debt_of(accounts.alice), 1_000);
+            assert_eq!(chest.position_health(accounts.alice), 10_000);
+            chest.repay(1_000);
+            assert_eq!(chest.debt_of(accounts.alice), 0);
+            assert_eq!(chest.position_collateral_of(accounts.alice), 0);
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 10_000);
+            assert_eq!(chest.total_supply(), 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "Position undercollateralized")]
+        fn open_position_undercollateralized() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.set_position_min_ratio(15_000);
+            chest.open_position(1_000, 700);
+        }
+
+        #[test]
+        #[should_panic(expected = "Below minimum mint amount")]
+        fn open_position_below_minimum_mint() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.set_min_mint_amount(100);
+            chest.open_position(1_000, 99);
+        }
+
+        #[test]
+        #[should_panic(expected = "Mint rate limit exceeded")]
+        fn open_position_rate_limit_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.set_rate_limits(500, 0);
+            chest.mint(300);
+            chest.open_position(1_000, 201);
+        }
+
+        #[test]
+        #[should_panic(expected = "Would breach min collateral ratio")]
+        fn open_position_breaches_min_collateral_ratio() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.set_min_collateral_ratio(20_000);
+            chest.open_position(1_000, 600);
+        }
+
+        #[test]
+        fn open_position_checks_its_own_ratio() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            chest.set_min_collateral_ratio(10_000);
+
+            // The pool still backs its own supply in full
+            chest.open_position(1_000, 100);
+            assert_eq!(chest.collateralization_ratio(), 10_000);
+            assert_eq!(chest.backing_ratio_bps(), 10_000);
+            assert_eq!(chest.can_mint(100), Ok(100));
+        }
+
+        #[test]
+        #[should_panic(expected = "Tokens not backed by the collateral pools")]
+        fn position_tokens_redeemed_against_pool() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(100);
+            chest.open_position(1_000, 500);
+
+            // Only the 100 pool-backed tokens can be redeemed for pool collateral
+            chest.redeem(100);
+            assert_eq!(chest.collateral_pool, 0);
+            chest.redeem(1);
+        }
+
+        #[test]
+        fn transfer_from_emits_approval() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.approve(accounts.bob, 500);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.transfer_from(accounts.alice, accounts.charlie, 200), Ok(()));
+            match decode_events().last() {
+                Some(Event::Approval(Approval { owner, spender, amount })) => {
+                    assert_eq!((*owner, *spender, *amount), (accounts.alice, accounts.bob, 300));
+                }
+                _ => panic!("Expected an Approval event"),
+            }
+        }
+
+        #[test]
+        fn mint_at_minimum_amount() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_min_mint_amount(100);
+            assert_eq!(chest.min_mint_amount(), 100);
+            chest.mint(100);
+            assert_eq!(chest.total_supply(), 100);
+        }
+
+        #[test]
+        #[should_panic(expected = "Below minimum mint amount")]
+        fn mint_below_minimum_amount() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_min_mint_amount(100);
+            chest.mint(99);
+        }
+
+        #[test]
+        fn redeem_returns_exact_collateral_deposited() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 150, 2, 10_000, 0);
+            chest.set_rounding_modes(RoundingMode::Down, RoundingMode::Down);
+            // 4.5 and 7.5 collateral, both rounded down
+            chest.mint(3);
+            chest.mint(5);
+            assert_eq!(chest.collateral_pool, 11);
+            assert_eq!(chest.collateral_contributed_by(accounts.alice), 11);
+
+            chest.redeem(8);
+            assert_eq!(chest.collateral_pool, 0);
+            assert_eq!(chest.collateral_contributed_by(accounts.alice), 0);
+        }
+
+        #[test]
+        fn partial_redeems_leave_no_dust() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 150, 2, 10_000, 0);
+            chest.mint(3);
+            assert_eq!(chest.collateral_pool, 5);
+            chest.redeem(1);
+            chest.redeem(1);
+            assert_eq!(chest.collateral_pool, 2);
+            chest.redeem(1);
+            assert_eq!(chest.collateral_pool, 0);
+        }
+
+        #[test]
+        fn capabilities_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert_eq!(chest.capabilities(), 0b1111);
+            assert_ne!(chest.capabilities() & CAPABILITY_PERMIT, 0);
+        }
+
+        #[test]
+        fn strict_approve_requires_reset() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.approve(accounts.bob, 100);
+            chest.approve(accounts.bob, 200);
+
+            chest.set_strict_approve(true);
+            chest.approve(accounts.bob, 0);
+            chest.approve(accounts.bob, 300);
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 300);
+        }
+
+        #[test]
+        #[should_panic(expected = "Must reset allowance to zero first")]
+        fn strict_approve_without_reset() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_strict_approve(true);
+            chest.approve(accounts.bob, 100);
+            chest.approve(accounts.bob, 200);
+        }
+
+        #[test]
+        fn update_price_within_bounds() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_price_bounds(1, 1_000);
+            chest.update_price(1);
+            chest.update_price(1_000);
+            assert_eq!(chest.collateral_price, 1_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Price out of bounds")]
+        fn update_price_to_zero() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_price_bounds(1, 1_000);
+            chest.update_price(0);
+        }
+
+        #[test]
+        #[should_panic(expected = "Price out of bounds")]
+        fn live_price_above_max() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_price_bounds(1, 1_000);
+            mock::set_price(accounts.django, 1_001);
+            chest.mint(1_000);
+        }
+
+        #[test]
+        fn redeem_to_third_party() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+
+            chest.redeem_to(400, accounts.charlie);
+            assert_eq!(chest.balance_of(accounts.alice), 600);
+            assert_eq!(mock::balance_of(accounts.django, accounts.charlie), 400);
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_000);
+            assert!(decode_events().iter().any(|event| matches!(
+                event,
+                Event::Redeemed(Redeemed { from, to, amount: 400, .. }) if *from == accounts.alice && *to == accounts.charlie
+            )));
+        }
+
+        #[test]
+        fn batch_mint_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+
+            let collateral = chest.batch_mint(vec![accounts.bob, accounts.charlie, accounts.eve], vec![100, 200, 300]);
+            assert_eq!(collateral, 600);
+            assert_eq!(chest.balance_of(accounts.bob), 100);
+            assert_eq!(chest.balance_of(accounts.charlie), 200);
+            assert_eq!(chest.balance_of(accounts.eve), 300);
+            assert_eq!(chest.total_supply(), 600);
+            assert_eq!(mock::balance_of(accounts.django, contract), 600);
+            assert_eq!(chest.collateral_contributed_by(accounts.alice), 600);
+
+            let minted = decode_events()
+                .iter()
+                .filter(|event| matches!(event, Event::Minted(_)))
+                .count();
+            assert_eq!(minted, 3);
+        }
+
+        #[test]
+        #[should_panic(expected = "Recipients and amounts length mismatch")]
+        fn batch_mint_length_mismatch() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.batch_mint(vec![accounts.bob, accounts.charlie], vec![100]);
+        }
+
+        #[test]
+        #[should_panic(expected = "Mint cooldown active")]
+        fn batch_mint_during_cooldown() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_mint_cooldown(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.mint_to(accounts.bob, 100);
+            chest.batch_mint(vec![accounts.charlie, accounts.bob], vec![100, 100]);
+        }
+
+        #[test]
+        fn timelocked_action_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_min_delay(1_000);
+            let action = TimelockAction::SetCollateralRatio(15_000);
+            let action_id = chest.action_id(action.clone());
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.queue_action(action_id, 6_000);
+            assert_eq!(chest.action_eta(action_id), Some(6_000));
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_000);
+            chest.execute_action(action);
+            assert_eq!(chest.collateral_ratio(), 15_000);
+            assert_eq!(chest.action_eta(action_id), None);
+            assert!(matches!(decode_events().last(), Some(Event::ActionExecuted(_))));
+        }
+
+        #[test]
+        #[should_panic(expected = "Action not ready")]
+        fn timelocked_action_too_early() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_min_delay(1_000);
+            let action = TimelockAction::SetCollateralRatio(15_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.queue_action(chest.action_id(action.clone()), 6_000);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_999);
+            chest.execute_action(action);
+        }
+
+        #[test]
+        #[should_panic(expected = "Action must go through the timelock")]
+        fn timelocked_action_called_directly() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_min_delay(1_000);
+            chest.set_collateral_ratio(15_000);
+        }
+
+        #[test]
+        fn collateral_config_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.update_price(150);
+            assert_eq!(chest.collateral_config(), (accounts.django, 150, 5_000));
+            assert_eq!(
+                chest.collateral_config(),
+                (chest.collateral_address(), chest.collateral_price(), chest.price_updated_at())
+            );
+        }
+
+        #[test]
+        fn compliant_transfer_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            mock::set_callback_result(accounts.frank, true);
+            mock::block(accounts.frank, accounts.charlie);
+            chest.set_compliance_contract(Some(accounts.frank));
+
+            assert_eq!(chest.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 100);
+        }
+
+        #[test]
+        fn non_compliant_transfer_fails() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            mock::set_callback_result(accounts.frank, true);
+            mock::block(accounts.frank, accounts.charlie);
+            chest.set_compliance_contract(Some(accounts.frank));
+
+            assert_eq!(chest.transfer(accounts.charlie, 100), Err(Error::TransferNotCompliant));
+            assert_eq!(chest.balance_of(accounts.charlie), 0);
+        }
+
+        #[test]
+        fn migrate_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert_eq!(chest.storage_version(), STORAGE_VERSION);
+
+            chest.mint(1_000);
+
+            // Storage as left behind by the previous code
+            chest.storage_version = STORAGE_VERSION - 1;
+            chest.holders = StorageVec::new();
+            chest.legacy_holders = vec![accounts.alice];
+            chest.held_raw_supply = 0;
+            chest.allow_zero_transfers = false;
+            chest.redemption_queued = 100;
+            chest.flash_fee_bps = 100;
+            chest.migrate();
+            assert_eq!(chest.storage_version(), STORAGE_VERSION);
+            assert!(chest.allow_zero_transfers);
+            assert_eq!(chest.redemption_queued, 0);
+            assert_eq!(chest.flash_fee_bps(), 0);
+            assert_eq!(chest.holders_page(0, 10), vec![accounts.alice]);
+            assert_eq!(chest.held_raw_supply, 1_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Storage already migrated")]
+        fn migrate_twice() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.storage_version = STORAGE_VERSION - 1;
+            chest.migrate();
+            chest.migrate();
+        }
+
+        #[test]
+        fn transfer_sweeps_dust() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_dust_threshold(10);
+
+            assert_eq!(chest.transfer(accounts.bob, 500), Ok(()));
+            assert_eq!(chest.balance_of(accounts.alice), 500);
+            assert_eq!(chest.transfer(accounts.bob, 491), Ok(()));
+            assert_eq!(chest.balance_of(accounts.alice), 0);
+            assert_eq!(chest.balance_of(accounts.bob), 1_000);
+            assert_eq!(chest.holders_count(), 1);
+        }
+
+        #[test]
+        fn zero_transfers_allowed_by_default() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert!(chest.allow_zero_transfers());
+            assert_eq!(chest.transfer(accounts.bob, 0), Ok(()));
+            assert!(matches!(decode_events().last(), Some(Event::Transfer(Transfer { amount: 0, .. }))));
+        }
+
+        #[test]
+        fn zero_transfers_disallowed() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_allow_zero_transfers(false);
+            assert_eq!(chest.transfer(accounts.bob, 0), Err(Error::ZeroAmount));
+        }
+
+        #[test]
+        fn swap_collateral_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            mock::set_balance(accounts.eve, accounts.frank, 5_000);
+            mock::set_swap_output(accounts.frank, 2_000);
+            chest.mint(1_000);
+
+            chest.swap_collateral(accounts.eve, 50, accounts.frank);
+            assert_eq!(chest.collateral_config().0, accounts.eve);
+            assert_eq!(chest.collateral_price(), 50);
+            assert_eq!(chest.collateral_pool, 2_000);
+            assert_eq!(mock::balance_of(accounts.django, accounts.frank), 1_000);
+            assert_eq!(mock::balance_of(accounts.django, contract), 0);
+            assert_eq!(mock::balance_of(accounts.eve, contract), 2_000);
+            assert_eq!(chest.backing_ratio_bps(), 10_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Swap would weaken backing")]
+        fn swap_collateral_weakening_backing() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            mock::set_balance(accounts.eve, accounts.frank, 5_000);
+            mock::set_swap_output(accounts.frank, 1_500);
+            chest.mint(1_000);
+            chest.swap_collateral(accounts.eve, 50, accounts.frank);
+        }
+
+        #[test]
+        #[should_panic(expected = "Collateral owed outside the pool")]
+        fn swap_collateral_with_open_position() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            mock::set_balance(accounts.eve, accounts.frank, 5_000);
+            mock::set_swap_output(accounts.frank, 2_000);
+            chest.mint(1_000);
+            chest.open_position(500, 100);
+            chest.swap_collateral(accounts.eve, 50, accounts.frank);
+        }
+
+        #[test]
+        fn velocity_limit_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.remaining_transfer_allowance(accounts.alice), u128::MAX);
+            chest.set_velocity_limit(500, 1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+
+            assert_eq!(chest.transfer(accounts.bob, 300), Ok(()));
+            assert_eq!(chest.remaining_transfer_allowance(accounts.alice), 200);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_500);
+            assert_eq!(chest.transfer(accounts.bob, 200), Ok(()));
+            assert_eq!(chest.remaining_transfer_allowance(accounts.alice), 0);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_000);
+            assert_eq!(chest.remaining_transfer_allowance(accounts.alice), 500);
+            assert_eq!(chest.transfer(accounts.bob, 500), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 1_000);
+        }
+
+        #[test]
+        fn velocity_limit_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_velocity_limit(500, 1_000);
+            assert_eq!(chest.transfer(accounts.bob, 500), Ok(()));
+            assert_eq!(chest.transfer(accounts.bob, 1), Err(Error::TransferVelocityExceeded));
+            assert_eq!(chest.remaining_transfer_allowance(accounts.alice), 0);
+        }
+
+        #[test]
+        fn redemption_queue_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(accounts.bob, 1_000), Ok(()));
+            // The pool now covers only half of what the supply redeems for
+            mock::set_price(accounts.django, 200);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.request_redeem(1_000);
+            assert_eq!(chest.balance_of(accounts.bob), 0);
+            assert_eq!(chest.total_supply(), 0);
+            assert_eq!(chest.pending_redemption_of(accounts.bob), 2_000);
+            chest.process_redemptions();
+            assert_eq!(chest.pending_redemption_of(accounts.bob), 2_000);
+            assert_eq!(chest.excess_collateral(), 0);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.donate_collateral(1_000);
+            chest.process_redemptions();
+            assert_eq!(chest.pending_redemption_of(accounts.bob), 0);
+            assert_eq!(mock::balance_of(accounts.django, accounts.bob), 2_000);
+            assert_eq!(chest.collateral_pool, 0);
+            assert!(matches!(decode_events().last(), Some(Event::RedemptionProcessed(RedemptionProcessed { collateral: 2_000, .. }))));
+        }
+
+        #[test]
+        #[should_panic(expected = "Redeem rate limit exceeded")]
+        fn request_redeem_rate_limit_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_rate_limits(0, 500);
+            chest.mint(1_000);
+            chest.redeem(300);
+            chest.request_redeem(201);
+        }
+
+        #[test]
+        fn mint_as_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_mint_allowance(accounts.bob, 500);
+            assert_eq!(chest.mint_allowance_of(accounts.bob), 500);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert!(!chest.has_role(MINTER_ROLE, accounts.bob));
+            chest.mint_as(accounts.charlie, 300);
+            assert_eq!(chest.balance_of(accounts.charlie), 300);
+            assert_eq!(chest.mint_allowance_of(accounts.bob), 200);
+            chest.mint_as(accounts.bob, 200);
+            assert_eq!(chest.mint_allowance_of(accounts.bob), 0);
+            assert_eq!(chest.total_supply(), 500);
+        }
+
+        #[test]
+        #[should_panic(expected = "Mint allowance exceeded")]
+        fn mint_as_exceeding_allowance() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_mint_allowance(accounts.bob, 500);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.mint_as(accounts.bob, 300);
+            chest.mint_as(accounts.bob, 201);
+        }
+
+        #[test]
+        fn max_mintable_for_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 150, 2, 15_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            // 1_000 * 100 * 10_000 / (150 * 15_000)
+            assert_eq!(chest.max_mintable_for(1_000), 444);
+            let collateral_charged = chest.mint_with_max_collateral(444, 1_000);
+            assert!(collateral_charged <= 1_000);
+            assert!(chest.collateral_for(445, 150, RoundingMode::Up).unwrap() > 1_000);
+
+            chest.set_max_supply(1_000);
+            assert_eq!(chest.max_mintable_for(10_000), 556);
+            chest.set_min_mint_amount(600);
+            assert_eq!(chest.max_mintable_for(10_000), 0);
+        }
+
+        #[test]
+        fn revoke_all_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.approve(accounts.bob, 100);
+            chest.increase_allowance(accounts.charlie, 200);
+            chest.approve_with_expiry(accounts.django, 300, 1_000);
+            chest.approve(accounts.bob, 150);
+            let events_before = decode_events().len();
+
+            chest.revoke_all();
+            for spender in [accounts.bob, accounts.charlie, accounts.django] {
+                assert_eq!(chest.allowance(accounts.alice, spender), 0);
+            }
+            assert_eq!(chest.allowance_expiry(accounts.alice, accounts.django), u64::MAX);
+            let events = decode_events();
+            assert_eq!(events.len(), events_before + 3);
+            assert!(events[events_before..]
+                .iter()
+                .all(|event| matches!(event, Event::Approval(Approval { amount: 0, .. }))));
+        }
+
+        #[test]
+        fn mul_div_round_works() {
+            let round = |rounding| mul_div_round(U256::from(7), U256::from(3), U256::from(4), rounding);
+            // 21 / 4 = 5.25
+            assert_eq!(round(RoundingMode::Down), Some(5));
+            assert_eq!(round(RoundingMode::Up), Some(6));
+            assert_eq!(round(RoundingMode::Nearest), Some(5));
+            assert_eq!(mul_div_round(U256::from(5), U256::from(3), U256::from(6), RoundingMode::Nearest), Some(3));
+            assert_eq!(mul_div_round(U256::from(8), U256::from(3), U256::from(4), RoundingMode::Up), Some(6));
+            assert_eq!(mul_div_round(U256::from(1), U256::from(1), U256::from(0), RoundingMode::Down), None);
+        }
+
+        #[test]
+        fn rounding_modes_work() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 175, 2, 10_000, 0);
+            assert_eq!(chest.rounding_modes(), (RoundingMode::Up, RoundingMode::Down));
+            // 1.75 collateral, rounded up
+            assert_eq!(chest.mint(1), 2);
+            chest.set_rounding_modes(RoundingMode::Down, RoundingMode::Down);
+            assert_eq!(chest.mint(1), 1);
+            chest.set_rounding_modes(RoundingMode::Nearest, RoundingMode::Down);
+            assert_eq!(chest.mint(1), 2);
+            assert_eq!(chest.collateral_pool, 5);
+
+            // Bob has no contributions on the collateral ledger, so the formula applies
+            assert_eq!(chest.transfer(accounts.bob, 3), Ok(()));
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.redeem(3);
+            // 5.25 collateral, rounded down
+            assert_eq!(chest.collateral_pool, 0);
+        }
+
+        #[test]
+        fn accrue_yield_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.excess_collateral(), 0);
+            chest.set_yield_source(Some(accounts.eve));
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.eve);
+            chest.accrue_yield(30);
+            chest.accrue_yield(20);
+            assert_eq!(chest.total_yield_accrued(), 50);
+            assert_eq!(chest.collateral_pool, 1_050);
+            assert_eq!(chest.excess_collateral(), 50);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.withdraw_collateral(50);
+            assert_eq!(chest.excess_collateral(), 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "Caller is not the yield source")]
+        fn accrue_yield_not_yield_source() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_yield_source(Some(accounts.eve));
+            chest.accrue_yield(30);
+        }
+
+        #[test]
+        fn psp22_metadata_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert_eq!(chest.token_name(), Some(chest.name()));
+            assert_eq!(chest.token_symbol(), Some(chest.symbol()));
+            assert_eq!(chest.token_decimals(), 18);
+        }
+
+        #[test]
+        fn psp22_transfers_work() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.psp22_transfer(accounts.bob, 100, vec![1, 2, 3]), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 100);
+            assert_eq!(chest.psp22_transfer(accounts.bob, 1_000, Vec::new()), Err(PSP22Error::InsufficientBalance));
+            assert_eq!(
+                chest.psp22_transfer(AccountId::default(), 100, Vec::new()),
+                Err(PSP22Error::ZeroRecipientAddress)
+            );
+
+            chest.approve(accounts.charlie, 200);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(chest.psp22_transfer_from(accounts.alice, accounts.bob, 150, Vec::new()), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 250);
+            assert_eq!(chest.allowance(accounts.alice, accounts.charlie), 50);
+            assert_eq!(
+                chest.psp22_transfer_from(accounts.alice, accounts.bob, 100, Vec::new()),
+                Err(PSP22Error::InsufficientAllowance)
+            );
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.pause();
+            assert_eq!(
+                chest.psp22_transfer(accounts.bob, 100, Vec::new()),
+                Err(PSP22Error::Custom("ContractPaused".to_string()))
+            );
+        }
+
+        #[test]
+        fn psp22_allowances_work() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            assert_eq!(chest.psp22_total_supply(), 1_000);
+            assert_eq!(chest.psp22_balance_of(accounts.alice), 1_000);
+
+            assert_eq!(chest.psp22_approve(accounts.bob, 100), Ok(()));
+            assert_eq!(chest.psp22_increase_allowance(accounts.bob, 50), Ok(()));
+            assert_eq!(chest.psp22_allowance(accounts.alice, accounts.bob), 150);
+            assert_eq!(chest.psp22_decrease_allowance(accounts.bob, 200), Err(PSP22Error::InsufficientAllowance));
+            assert_eq!(chest.psp22_decrease_allowance(accounts.bob, 150), Ok(()));
+            assert_eq!(chest.psp22_allowance(accounts.alice, accounts.bob), 0);
+            assert_eq!(chest.psp22_approve(AccountId::default(), 100), Err(PSP22Error::ZeroRecipientAddress));
+
+            // Variant indices follow the standard's `PSP22Error`
+            assert_eq!(scale::Encode::encode(&PSP22Error::InsufficientBalance), vec![1]);
+            assert_eq!(scale::Encode::encode(&PSP22Error::ZeroRecipientAddress), vec![3]);
+        }
+
+        #[test]
+        fn reserve_limits_excess_collateral() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_yield_source(Some(accounts.alice));
+            chest.accrue_yield(200);
+            assert_eq!(chest.excess_collateral(), 200);
+
+            // 10% of the 1_200 pool stays put
+            chest.set_reserve(1_000);
+            assert_eq!(chest.reserve_bps(), 1_000);
+            assert_eq!(chest.excess_collateral(), 80);
+            chest.withdraw_collateral(80);
+            assert_eq!(chest.collateral_pool, 1_120);
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough excess collateral")]
+        fn reserve_withdraw_into_buffer() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100);
-            let amount = 100_000;
-            chest.mint(amount);
-            chest.redeem(amount / 2);
-            assert_eq!(chest.total_supply(), amount / 2);
-            assert_eq!(chest.balance_of(accounts.alice), amount / 2);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_yield_source(Some(accounts.alice));
+            chest.accrue_yield(200);
+            chest.set_reserve(1_000);
+            chest.withdraw_collateral(81);
         }
 
         #[test]
-        #[should_panic(expected = "Not enough balance to redeem")]
-        fn redeem_not_enough_balance() {
+        fn mint_and_redeem_events_carry_collateral() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100);
-            let amount = 100_000;
-            chest.mint(amount);
-            chest.redeem(amount * 2);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 250, 2, 15_000, 0);
+            chest.set_redeem_fee(100);
+            chest.mint(1_000);
+            // 1_000 * 2.50 * 150%
+            assert!(matches!(
+                decode_events()[0],
+                Event::Minted(Minted { amount: 1_000, collateral: 3_750, .. })
+            ));
+
+            assert_eq!(chest.transfer(accounts.bob, 400), Ok(()));
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.redeem(400);
+            // 400 * 2.50 * 150% less the 1% fee
+            assert!(decode_events().iter().any(|event| matches!(
+                event,
+                Event::Redeemed(Redeemed { amount: 400, collateral: 1_485, .. })
+            )));
         }
 
         #[test]
-        #[should_panic(expected = "Not enough collateral in the pool")]
-        fn redeem_not_enough_collateral() {
+        fn whitelist_works() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100);
-            let amount = 100_000;
-            chest.mint(amount);
-            chest.redeem(amount);
-            chest.redeem(amount);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_whitelist_enabled(true);
+            chest.add_to_whitelist(accounts.alice);
+            chest.add_to_whitelist(accounts.bob);
+            assert!(matches!(decode_events().last(), Some(Event::AddedToWhitelist(_))));
+            assert_eq!(chest.transfer(accounts.bob, 100), Ok(()));
+
+            chest.approve(accounts.charlie, 100);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.charlie);
+            // Only the parties to the transfer have to be whitelisted
+            assert_eq!(chest.transfer_from(accounts.alice, accounts.bob, 100), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 200);
         }
 
         #[test]
-        fn transfer_works() {
+        fn whitelist_blocks_unlisted_recipient() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100);
-            let amount = 100_000;
-            chest.mint(amount);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_whitelist_enabled(true);
+            chest.add_to_whitelist(accounts.alice);
+            chest.add_to_whitelist(accounts.bob);
+            chest.remove_from_whitelist(accounts.bob);
+            assert_eq!(chest.transfer(accounts.bob, 100), Err(Error::NotWhitelisted));
+        }
 
-            // Transfer to Bob
-            chest.transfer(accounts.bob, amount / 2);
-            assert_eq!(chest.balance_of(accounts.alice), amount / 2);
-            assert_eq!(chest.balance_of(accounts.bob), amount / 2);
+        #[test]
+        fn circulating_supply_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint_to(accounts.eve, 600);
+            chest.mint_to(accounts.bob, 400);
+            assert_eq!(chest.circulating_supply(), 1_000);
 
-            // Transfer from Bob to Charlie
-            chest.approve(accounts.bob, amount / 4);
-            chest.transfer_from(accounts.bob, accounts.charlie, amount / 4);
-            assert_eq!(chest.balance_of(accounts.bob), amount / 4);
-            assert_eq!(chest.balance_of(accounts.charlie), amount / 4);
+            chest.add_non_circulating(accounts.eve);
+            chest.add_non_circulating(accounts.eve);
+            assert_eq!(chest.non_circulating_accounts(), vec![accounts.eve]);
+            assert_eq!(chest.circulating_supply(), 400);
+            assert_eq!(chest.total_supply(), 1_000);
+
+            chest.remove_non_circulating(accounts.eve);
+            assert_eq!(chest.circulating_supply(), 1_000);
         }
 
         #[test]
-        #[should_panic(expected = "Not enough allowance")]
-        fn transfer_not_enough_allowance() {
+        fn check_health_pauses_on_insolvency() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100);
-            let amount = 100_000;
-            chest.mint(amount);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.set_insolvency_threshold(8_000);
+            chest.mint(1_000);
 
-            chest.transfer(accounts.bob, amount / 2);
-            chest.approve(accounts.bob, amount / 4);
-            chest.transfer_from(accounts.bob, accounts.charlie, amount / 2);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.check_health();
+            assert!(!chest.paused());
+
+            mock::set_price(accounts.django, 50);
+            chest.check_health();
+            assert!(chest.paused());
+            assert!(matches!(
+                decode_events().last(),
+                Some(Event::AutoPaused(AutoPaused { backing_ratio: 5_000 }))
+            ));
         }
 
         #[test]
-        #[should_panic(expected = "Not enough balance")]
-        fn transfer_not_enough_balance() {
+        #[should_panic(expected = "Price data is stale")]
+        fn check_health_keeps_price_stale() {
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
-            let mutchest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100);
-            let amount = 100_000;
-            chest.mint(amount);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_max_price_age(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.update_price(100);
 
-            chest.transfer(accounts.bob, amount * 2);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_001);
+            chest.check_health();
+            assert_eq!(chest.price_updated_at(), 5_000);
+            chest.mint(1_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Price out of bounds")]
+        fn check_health_price_out_of_bounds() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_price_bounds(50, 200);
+            mock::set_price(accounts.django, 10);
+            chest.check_health();
+        }
+
+        #[test]
+        #[should_panic(expected = "Reentrant call")]
+        fn check_health_reentrant_call() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_insolvency_threshold(8_000);
+            chest.mint(1_000);
+
+            // State a flash mint callback would observe
+            chest.locked = true;
+            chest.check_health();
+        }
+
+        #[test]
+        fn max_tx_amount_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_max_tx_amount(100, true);
+            assert_eq!(chest.max_tx_amount(), 100);
+            // The owner is exempt
+            assert_eq!(chest.transfer(accounts.bob, 500), Ok(()));
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(chest.balance_of(accounts.charlie), 100);
+        }
+
+        #[test]
+        fn max_tx_amount_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_max_tx_amount(100, false);
+            assert_eq!(chest.transfer(accounts.bob, 101), Err(Error::MaxTxAmountExceeded));
+        }
+
+        #[test]
+        fn transfer_from_and_burn_from_spend_allowance_alike() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.approve(accounts.bob, 500);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.transfer_from(accounts.alice, accounts.charlie, 200), Ok(()));
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 300);
+            let after_transfer = decode_events();
+            chest.burn_from(accounts.alice, 200);
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 100);
+            let after_burn = decode_events();
+
+            // Both emit the remaining allowance the same way
+            let approvals = |events: &[Event]| -> Vec<u128> {
+                events
+                    .iter()
+                    .filter_map(|event| match event {
+                        Event::Approval(Approval { owner, spender, amount }) if *owner == accounts.alice && *spender == accounts.bob => Some(*amount),
+                        _ => None,
+                    })
+                    .collect()
+            };
+            assert_eq!(approvals(&after_transfer), vec![500, 300]);
+            assert_eq!(approvals(&after_burn), vec![500, 300, 100]);
+
+            assert_eq!(chest.transfer_from(accounts.alice, accounts.charlie, 101), Err(Error::InsufficientAllowance));
+            assert_eq!(chest.allowance(accounts.alice, accounts.bob), 100);
+        }
+
+        #[test]
+        fn redeem_delay_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            chest.set_redeem_delay(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+
+            chest.redeem(400);
+            assert_eq!(chest.balance_of(accounts.alice), 600);
+            assert_eq!(chest.collateral_pool, 600);
+            assert_eq!(chest.pending_collateral_of(accounts.alice), (400, 6_000));
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_000);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_000);
+            chest.claim_redeemed_collateral();
+            assert_eq!(chest.pending_collateral_of(accounts.alice), (0, 0));
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_400);
+        }
+
+        #[test]
+        fn redeem_delay_escrows_queued_redemptions() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            chest.set_redeem_delay(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+
+            chest.request_redeem(400);
+            chest.process_redemptions();
+            assert_eq!(chest.pending_redemption_of(accounts.alice), 0);
+            assert_eq!(chest.collateral_pool, 600);
+            assert_eq!(chest.pending_collateral_of(accounts.alice), (400, 6_000));
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_000);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_000);
+            chest.claim_redeemed_collateral();
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_400);
+        }
+
+        #[test]
+        #[should_panic(expected = "Collateral still locked")]
+        fn redeem_delay_claim_too_early() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            chest.set_redeem_delay(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.redeem(400);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_999);
+            chest.claim_redeemed_collateral();
+        }
+
+        #[test]
+        fn zero_decimals_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 0, accounts.alice, 150, 2, 10_000, 0);
+            // The collateral math only depends on the price decimals
+            assert_eq!(chest.mint(10), 15);
+            assert!(chest.transfer_units(accounts.bob, 3, 0));
+            assert_eq!(chest.balance_of(accounts.bob), 3);
+            chest.redeem(4);
+            assert_eq!(chest.balance_of(accounts.alice), 3);
+            assert_eq!(chest.collateral_pool, 9);
+        }
+
+        #[test]
+        #[should_panic(expected = "Fractional part too large")]
+        fn zero_decimals_transfer_units_fractional() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 0, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(10);
+            chest.transfer_units(accounts.bob, 1, 1);
+        }
+
+        #[test]
+        #[should_panic(expected = "Decimals too large")]
+        fn decimals_too_large() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            Chest::new("Chest".to_string(), "CHEST".to_string(), 39, accounts.alice, 100, 2, 10_000, 0);
+        }
+
+        #[test]
+        fn can_mint_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 125, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            assert_eq!(chest.can_mint(1_000), Ok(1_250));
+            assert_eq!(chest.mint(1_000), 1_250);
+
+            chest.set_min_mint_amount(100);
+            assert_eq!(chest.can_mint(99), Err(Error::BelowMinimumMint));
+            chest.set_min_mint_amount(0);
+
+            chest.set_max_supply(1_500);
+            assert_eq!(chest.can_mint(501), Err(Error::MaxSupplyExceeded));
+            chest.set_max_supply(0);
+            assert_eq!(chest.can_mint(u128::MAX), Err(Error::ArithmeticOverflow));
+
+            chest.set_rate_limits(1_500, 0);
+            assert_eq!(chest.can_mint(501), Err(Error::MintRateLimitExceeded));
+            assert_eq!(chest.can_mint(500), Ok(625));
+            chest.set_rate_limits(0, 0);
+
+            chest.set_mint_cooldown(1_000);
+            chest.mint(100);
+            assert_eq!(chest.can_mint(100), Err(Error::MintCooldownActive));
+            chest.set_mint_cooldown(0);
+
+            chest.set_price_bounds(1, 100);
+            assert_eq!(chest.can_mint(100), Err(Error::PriceOutOfBounds));
+            chest.set_price_bounds(0, u128::MAX);
+
+            chest.set_max_price_age(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            assert_eq!(chest.can_mint(100), Err(Error::StalePrice));
+            // A new price would be recorded as fresh
+            mock::set_price(accounts.django, 130);
+            assert_eq!(chest.can_mint(100), Ok(130));
+            chest.set_max_price_age(0);
+
+            chest.set_min_collateral_ratio(20_000);
+            assert_eq!(chest.can_mint(100), Err(Error::InsufficientCollateral));
+            chest.set_min_collateral_ratio(0);
+
+            chest.pause();
+            assert_eq!(chest.can_mint(100), Err(Error::ContractPaused));
+            chest.unpause();
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.can_mint(100), Err(Error::MissingRole));
+        }
+
+        #[test]
+        fn fee_exempt_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(10_000);
+            chest.set_transfer_fee(100, accounts.eve);
+            chest.set_fee_exempt(accounts.bob, true);
+            assert!(chest.is_fee_exempt(accounts.bob));
+
+            assert_eq!(chest.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(chest.balance_of(accounts.bob), 1_000);
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.transfer(accounts.charlie, 500), Ok(()));
+            assert_eq!(chest.balance_of(accounts.charlie), 500);
+            assert_eq!(chest.balance_of(accounts.eve), 0);
+
+            // Neither party exempt
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.charlie);
+            assert_eq!(chest.transfer(accounts.django, 500), Ok(()));
+            assert_eq!(chest.balance_of(accounts.django), 495);
+            assert_eq!(chest.balance_of(accounts.eve), 5);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.alice);
+            chest.set_fee_exempt(accounts.bob, false);
+            assert!(!chest.is_fee_exempt(accounts.bob));
+        }
+
+        #[test]
+        fn collateral_floor_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_collateral_floor(300);
+            assert_eq!(chest.collateral_floor(), 300);
+            chest.redeem(700);
+            assert_eq!(chest.collateral_pool, 300);
+        }
+
+        #[test]
+        #[should_panic(expected = "Would breach collateral floor")]
+        fn collateral_floor_redeem_below() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_collateral_floor(300);
+            chest.redeem(700);
+            chest.redeem(1);
+        }
+
+        #[test]
+        fn collateral_floor_holds_back_queued_redemptions() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(1_000);
+            chest.set_collateral_floor(300);
+            chest.request_redeem(800);
+            assert_eq!(chest.pending_redemption_of(accounts.alice), 800);
+            assert_eq!(chest.collateral_pool, 1_000);
+
+            chest.set_collateral_floor(200);
+            chest.process_redemptions();
+            assert_eq!(chest.pending_redemption_of(accounts.alice), 0);
+            assert_eq!(chest.collateral_pool, 200);
         }
-    }
-}
 
+        #[test]
+        #[should_panic(expected = "Would breach collateral floor")]
+        fn collateral_floor_withdraw_below() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(100);
+            chest.set_yield_source(Some(accounts.alice));
+            chest.accrue_yield(500);
+            chest.set_collateral_floor(300);
+            chest.withdraw_collateral(301);
+        }
+
+        #[test]
+        fn multisig_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let owners = vec![accounts.alice, accounts.bob, accounts.charlie];
+            let mut chest = Chest::new_with_owners("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0, owners.clone(), 2);
+            assert_eq!(chest.multisig_owners(), owners);
+            assert_eq!(chest.threshold(), 2);
+
+            let transaction_id = chest.submit_transaction(MultisigAction::SetMaxSupply(5_000));
+            assert_eq!(chest.confirmation_count(transaction_id), 1);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.charlie);
+            chest.confirm_transaction(transaction_id);
+            assert_eq!(chest.max_supply(), 0);
+            chest.execute_transaction(transaction_id);
+            assert_eq!(chest.max_supply(), 5_000);
+            assert!(matches!(decode_events().last(), Some(Event::TransactionExecuted(_))));
+        }
+
+        #[test]
+        #[should_panic(expected = "Not enough confirmations")]
+        fn multisig_below_threshold() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let owners = vec![accounts.alice, accounts.bob, accounts.charlie];
+            let mut chest = Chest::new_with_owners("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0, owners, 2);
+            let transaction_id = chest.submit_transaction(MultisigAction::SetCodeHash([0x01; 32]));
+            chest.execute_transaction(transaction_id);
+        }
+
+        #[test]
+        #[should_panic(expected = "Action must go through the multisig")]
+        fn multisig_blocks_direct_owner_action() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let owners = vec![accounts.alice, accounts.bob, accounts.charlie];
+            let mut chest = Chest::new_with_owners("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0, owners, 2);
+            chest.set_max_supply(5_000);
+        }
+
+        #[test]
+        #[should_panic(expected = "Duplicate multisig owner")]
+        fn multisig_duplicate_owner() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let owners = vec![accounts.alice, accounts.alice];
+            Chest::new_with_owners("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0, owners, 2);
+        }
+
+        #[test]
+        fn multisig_timelocked_action_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let owners = vec![accounts.alice, accounts.bob, accounts.charlie];
+            let mut chest = Chest::new_with_owners("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0, owners, 2);
+            chest.set_min_delay(1_000);
+            let code_hash = [0x02; 32];
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.queue_action(chest.action_id(TimelockAction::SetCodeHash(code_hash)), 6_000);
+            let transaction_id = chest.submit_transaction(MultisigAction::SetCodeHash(code_hash));
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.confirm_transaction(transaction_id);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_000);
+            chest.execute_transaction(transaction_id);
+            assert_eq!(mock::code_hash(), Some(code_hash));
+        }
+
+        #[test]
+        #[should_panic(expected = "Action not queued")]
+        fn multisig_skipping_timelock() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let owners = vec![accounts.alice, accounts.bob, accounts.charlie];
+            let mut chest = Chest::new_with_owners("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0, owners, 2);
+            chest.set_min_delay(1_000);
+            let transaction_id = chest.submit_transaction(MultisigAction::SetCodeHash([0x02; 32]));
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            chest.confirm_transaction(transaction_id);
+            chest.execute_transaction(transaction_id);
+        }
+
+        #[test]
+        #[should_panic(expected = "Action must go through the multisig")]
+        fn timelock_skipping_multisig() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let owners = vec![accounts.alice, accounts.bob, accounts.charlie];
+            let mut chest = Chest::new_with_owners("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0, owners, 2);
+            chest.set_min_delay(1_000);
+            let action = TimelockAction::WithdrawCollateral(0);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            chest.queue_action(chest.action_id(action.clone()), 6_000);
+
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(6_000);
+            chest.execute_action(action);
+        }
+
+        #[test]
+        fn allowances_of_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert_eq!(chest.allowances_of(accounts.alice), vec![]);
+            chest.approve(accounts.bob, 100);
+            chest.approve(accounts.charlie, 200);
+            chest.approve(accounts.django, 300);
+            chest.approve(accounts.charlie, 0);
+            assert_eq!(chest.allowances_of(accounts.alice), vec![(accounts.bob, 100), (accounts.django, 300)]);
+            assert_eq!(chest.allowances_of(accounts.bob), vec![]);
+        }
+
+        #[test]
+        fn submit_signed_price_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_oracle_pubkey(Some(ORACLE_PUBKEY));
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(1_500);
+
+            chest.submit_signed_price(150, 1_000, ORACLE_PRICE_SIGNATURE);
+            assert_eq!(chest.collateral_price(), 150);
+            // The collateral contract's price no longer counts
+            mock::set_price(accounts.django, 200);
+            assert_eq!(chest.fetch_price(), 150);
+        }
+
+        #[test]
+        #[should_panic(expected = "Invalid signature")]
+        fn submit_signed_price_wrong_signer() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_oracle_pubkey(Some(ORACLE_PUBKEY));
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(1_500);
+            chest.submit_signed_price(150, 1_000, FOREIGN_PRICE_SIGNATURE);
+        }
+
+        #[test]
+        #[should_panic(expected = "Price data is stale")]
+        fn submit_signed_price_replay() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_oracle_pubkey(Some(ORACLE_PUBKEY));
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(1_500);
+            chest.submit_signed_price(150, 1_000, ORACLE_PRICE_SIGNATURE);
+            chest.submit_signed_price(150, 1_000, ORACLE_PRICE_SIGNATURE);
+        }
+
+        #[test]
+        #[should_panic(expected = "Price timestamp in the future")]
+        fn submit_signed_price_from_the_future() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            ink_env::test::set_callee::<ink_env::DefaultEnvironment>(accounts.eve);
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            chest.set_oracle_pubkey(Some(ORACLE_PUBKEY));
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(999);
+            chest.submit_signed_price(150, 1_000, ORACLE_PRICE_SIGNATURE);
+        }
+
+        #[test]
+        fn collateral_cap_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            assert_eq!(chest.collateral_headroom(), u128::MAX);
+            chest.set_max_collateral_pool(1_000);
+            assert_eq!(chest.max_collateral_pool(), 1_000);
+            chest.mint(600);
+            assert_eq!(chest.collateral_headroom(), 400);
+            chest.donate_collateral(100);
+            chest.mint(300);
+            assert_eq!(chest.collateral_headroom(), 0);
+
+            chest.set_max_collateral_pool(1_500);
+            chest.mint(500);
+            assert_eq!(chest.collateral_pool, 1_500);
+        }
+
+        #[test]
+        #[should_panic(expected = "Collateral cap reached")]
+        fn collateral_cap_exceeded() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_max_collateral_pool(1_000);
+            chest.mint(1_000);
+            chest.mint(1);
+        }
+
+        #[test]
+        fn can_mint_collateral_cap_reached() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_max_collateral_pool(1_000);
+            chest.mint(600);
+            assert_eq!(chest.can_mint(400), Ok(400));
+            assert_eq!(chest.can_mint(401), Err(Error::CollateralCapReached));
+        }
+
+        #[test]
+        fn sweep_fees_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.mint(10_000);
+            chest.set_transfer_fee(100, accounts.eve);
+            assert_eq!(chest.transfer(accounts.bob, 5_000), Ok(()));
+            assert_eq!(chest.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(chest.fees_collected_by(accounts.eve), 60);
+
+            assert_eq!(chest.sweep_fees(accounts.frank), 60);
+            assert_eq!(chest.unswept_fees_of(accounts.eve), 0);
+            assert_eq!(chest.fees_collected_by(accounts.eve), 60);
+            assert_eq!(chest.total_fees_collected(), 60);
+            assert_eq!(chest.balance_of(accounts.eve), 0);
+            assert_eq!(chest.balance_of(accounts.frank), 60);
+            assert!(decode_events().iter().any(|event| matches!(event, Event::FeesSwept(FeesSwept { amount: 60, .. }))));
+
+            assert_eq!(chest.sweep_fees(accounts.frank), 0);
+            assert_eq!(chest.balance_of(accounts.frank), 60);
+        }
+
+        #[test]
+        fn sweep_fees_includes_flash_mint_fees() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+            chest.set_transfer_fee(0, accounts.eve);
+            chest.set_flash_fee(100);
+            chest.mint_to(accounts.frank, 10);
+            mock::set_callback_result(accounts.frank, true);
+            chest.flash_mint(1_000, accounts.frank, Vec::new());
+            assert_eq!(chest.fees_collected_by(accounts.eve), 10);
+
+            let events = decode_events().len();
+            assert_eq!(chest.sweep_fees(accounts.eve), 0);
+            assert_eq!(decode_events().len(), events);
+            assert_eq!(chest.sweep_fees(accounts.bob), 10);
+            assert_eq!(chest.balance_of(accounts.bob), 10);
+            assert_eq!(chest.balance_of(accounts.eve), 0);
+        }
+
+        #[test]
+        fn redeem_all_works() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            assert_eq!(chest.redeem_all(), (1_000, 0, 0));
+            assert_eq!(chest.balance_of(accounts.alice), 0);
+            assert_eq!(chest.collateral_pool, 0);
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 10_000);
+        }
+
+        #[test]
+        fn redeem_all_with_redeem_delay() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            chest.set_redeem_delay(1_000);
+            ink_env::test::set_block_timestamp::<ink_env::DefaultEnvironment>(5_000);
+            assert_eq!(chest.redeem_all(), (0, 1_000, 0));
+            assert_eq!(chest.pending_collateral_of(accounts.alice), (1_000, 6_000));
+            assert_eq!(mock::balance_of(accounts.django, accounts.alice), 9_000);
+        }
+
+        #[test]
+        fn redeem_all_queues_what_the_pool_cannot_cover() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            assert_eq!(chest.transfer(accounts.bob, 1_000), Ok(()));
+            chest.set_collateral_floor(200);
+            mock::set_price(accounts.django, 200);
+
+            ink_env::test::set_caller::<ink_env::DefaultEnvironment>(accounts.bob);
+            assert_eq!(chest.redeem_all(), (800, 0, 1_200));
+            assert_eq!(chest.balance_of(accounts.bob), 0);
+            assert_eq!(chest.total_supply(), 0);
+            assert_eq!(chest.collateral_pool, 200);
+            assert_eq!(mock::balance_of(accounts.django, accounts.bob), 800);
+            assert_eq!(chest.pending_redemption_of(accounts.bob), 1_200);
+        }
+
+        #[test]
+        #[should_panic(expected = "Decimals too large")]
+        fn decimals_above_36_rejected() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            Chest::new("Chest".to_string(), "CHEST".to_string(), 37, accounts.alice, 100, 2, 10_000, 0);
+        }
+
+        #[test]
+        fn decimals_36_accepted() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 36, accounts.alice, 100, 2, 10_000, 0);
+            assert_eq!(chest.decimals(), 36);
+        }
+
+        #[test]
+        #[should_panic(expected = "Collateral price must be positive")]
+        fn zero_collateral_price_rejected() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.alice, 0, 2, 10_000, 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "Name must not be empty")]
+        fn empty_name_rejected() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            Chest::new(String::new(), "CHEST".to_string(), 18, accounts.alice, 100, 2, 10_000, 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "Symbol must not be empty")]
+        fn empty_symbol_rejected() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            Chest::new("Chest".to_string(), String::new(), 18, accounts.alice, 100, 2, 10_000, 0);
+        }
+
+        #[test]
+        fn collateral_reconciliation_matches_after_mint() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            assert_eq!(chest.collateral_reconciliation(), (0, 0));
+            chest.mint(1_000);
+            assert_eq!(chest.collateral_reconciliation(), (1_000, 1_000));
+        }
+
+        #[test]
+        fn collateral_reconciliation_reports_unaccounted_transfer() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+
+            assert!(mock::transfer(accounts.django, accounts.alice, contract, 250));
+            assert_eq!(chest.collateral_reconciliation(), (1_000, 1_250));
+        }
 
+        #[test]
+        fn collateral_reconciliation_counts_every_liability() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>().expect("Cannot get accounts");
+            let mut chest = Chest::new("Chest".to_string(), "CHEST".to_string(), 18, accounts.django, 100, 2, 10_000, 0);
+            mock::set_balance(accounts.django, accounts.alice, 10_000);
+            chest.mint(1_000);
+            chest.set_redeem_delay(100);
+            chest.redeem(100);
+            chest.open_position(500, 100);
+            chest.distribute(300);
+            assert_eq!(chest.collateral_reconciliation(), (1_800, 1_800));
 
+            chest.claim_dividend();
+            chest.repay(100);
+            assert_eq!(chest.collateral_reconciliation(), (1_000, 1_000));
+        }
+    }
+}